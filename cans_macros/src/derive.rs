@@ -0,0 +1,380 @@
+use proc_macro::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+
+use crate::compile_error;
+
+/// A struct field as seen by `#[derive(Context)]`.
+struct Field {
+    /// The member used to access the field: a name or a tuple index.
+    member: String,
+    /// The field type, as source text.
+    ty: String,
+    /// The placeholder key, after `#[cans(rename = "...")]`.
+    key: String,
+    skip: bool,
+    flatten: bool,
+}
+
+/// Expands `#[derive(Context)]` for a struct.
+pub(crate) fn derive_context(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err((message, span)) => compile_error(&message, span),
+    }
+}
+
+type Error = (String, Span);
+
+fn expand(input: TokenStream) -> Result<TokenStream, Error> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut position = 0;
+
+    // Outer attributes, with the `#[cans(crate = "...")]` path, and visibility.
+    let mut krate = String::from("::cans");
+    while is_punct(tokens.get(position), '#') {
+        if let Some(TokenTree::Group(attribute)) = tokens.get(position + 1) {
+            parse_container_attribute(attribute, &mut krate)?;
+        }
+        position += 2;
+    }
+    skip_visibility(&tokens, &mut position);
+
+    match tokens.get(position) {
+        Some(TokenTree::Ident(keyword)) if keyword.to_string() == "struct" => position += 1,
+        Some(token) => return Err(("`Context` can only be derived for structs".into(), token.span())),
+        None => return Err(("expected a struct".into(), Span::call_site())),
+    }
+
+    let name = match tokens.get(position) {
+        Some(TokenTree::Ident(name)) => name.to_string(),
+        _ => return Err(("expected a struct name".into(), Span::call_site())),
+    };
+    position += 1;
+
+    let (impl_generics, type_generics, type_params) = parse_generics(&tokens, &mut position)?;
+
+    // The where clause comes before a named body (`{ ... }`) or after a tuple body (`( ... )`).
+    let mut where_clause = String::new();
+    let mut in_where = false;
+    let mut body = None;
+    while let Some(token) = tokens.get(position) {
+        match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => body = Some(group.clone()),
+            TokenTree::Group(group)
+                if group.delimiter() == Delimiter::Parenthesis && !in_where && body.is_none() =>
+            {
+                body = Some(group.clone())
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "where" => in_where = true,
+            TokenTree::Punct(punct) if punct.as_char() == ';' => {}
+            token => {
+                where_clause.push_str(&token.to_string());
+                where_clause.push(' ');
+            }
+        }
+        position += 1;
+    }
+    let where_clause = where_clause.trim().trim_end_matches(',').to_string();
+
+    let fields = match &body {
+        Some(group) if group.delimiter() == Delimiter::Brace => parse_fields(group.stream(), true)?,
+        Some(group) => parse_fields(group.stream(), false)?,
+        None => Vec::new(),
+    };
+
+    let mut arms = String::new();
+    let mut flattened = String::new();
+    let mut bounds: Vec<String> = Vec::new();
+    for field in fields.iter().filter(|field| !field.skip) {
+        // Only fields that use a type parameter need a bound; bounds on concrete types that
+        // mention lifetimes would pin those lifetimes.
+        let generic = field.ty.split(|c: char| !c.is_alphanumeric() && c != '_').any(|word| type_params.iter().any(|param| param == word));
+        if field.flatten {
+            flattened.push_str(&format!(
+                "if let ::core::option::Option::Some(value) = {krate}::content::Context::get(&self.{}, key) {{ return ::core::option::Option::Some(value); }}\n",
+                field.member
+            ));
+            if generic {
+                bounds.push(format!("{}: {krate}::content::Context", field.ty));
+            }
+        } else {
+            arms.push_str(&format!(
+                "{:?} => return ::core::option::Option::Some({krate}::content::TemplateValue::to_value(&self.{})),\n",
+                field.key, field.member
+            ));
+            if generic {
+                bounds.push(format!("{}: {krate}::content::TemplateValue", field.ty));
+            }
+        }
+    }
+
+    let mut predicates: Vec<String> = Vec::new();
+    if !where_clause.is_empty() {
+        predicates.push(where_clause);
+    }
+    predicates.extend(bounds);
+    let where_clause = if predicates.is_empty() {
+        String::new()
+    } else {
+        format!("where {}", predicates.join(", "))
+    };
+
+    let output = format!(
+        "impl{impl_generics} {krate}::content::Context for {name}{type_generics} {where_clause} {{
+            #[allow(unreachable_code, unused_variables)]
+            fn get(&self, key: &str) -> ::core::option::Option<{krate}::content::Value<'_>> {{
+                match key {{
+                    {arms}
+                    _ => {{}}
+                }}
+                {flattened}
+                ::core::option::Option::None
+            }}
+        }}
+
+        impl{impl_generics} {krate}::content::TemplateValue for {name}{type_generics} {where_clause} {{
+            fn to_value(&self) -> {krate}::content::Value<'_> {{
+                {krate}::content::Value::Context(self)
+            }}
+        }}"
+    );
+
+    Ok(crate::tokens(&output))
+}
+
+/// Tracks `<` / `>` nesting in a token sequence, ignoring the `>` of `->`.
+#[derive(Default)]
+struct Angles {
+    depth: usize,
+    after_dash: bool,
+}
+
+impl Angles {
+    fn update(&mut self, token: &TokenTree) {
+        let after_dash = std::mem::take(&mut self.after_dash);
+        if let TokenTree::Punct(punct) = token {
+            match punct.as_char() {
+                '<' => self.depth += 1,
+                '>' if !after_dash => self.depth = self.depth.saturating_sub(1),
+                '-' => self.after_dash = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_punct(token: Option<&TokenTree>, c: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == c)
+}
+
+fn skip_visibility(tokens: &[TokenTree], position: &mut usize) {
+    if let Some(TokenTree::Ident(ident)) = tokens.get(*position)
+        && ident.to_string() == "pub"
+    {
+        *position += 1;
+        if let Some(TokenTree::Group(group)) = tokens.get(*position)
+            && group.delimiter() == Delimiter::Parenthesis
+        {
+            *position += 1;
+        }
+    }
+}
+
+/// Parses `<...>` after the struct name into impl generics (with bounds, without defaults),
+/// type generics (names only) and the names of the type parameters.
+fn parse_generics(tokens: &[TokenTree], position: &mut usize) -> Result<(String, String, Vec<String>), Error> {
+    if !is_punct(tokens.get(*position), '<') {
+        return Ok((String::new(), String::new(), Vec::new()));
+    }
+    *position += 1;
+
+    let mut params: Vec<Vec<TokenTree>> = vec![Vec::new()];
+    let mut angles = Angles::default();
+    loop {
+        let Some(token) = tokens.get(*position) else {
+            return Err(("unterminated generics".into(), Span::call_site()));
+        };
+        *position += 1;
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '>' && angles.depth == 0 && !angles.after_dash => break,
+            TokenTree::Punct(punct) if punct.as_char() == ',' && angles.depth == 0 => params.push(Vec::new()),
+            token => {
+                angles.update(token);
+                params.last_mut().unwrap().push(token.clone());
+            }
+        }
+    }
+
+    let mut impl_params = Vec::new();
+    let mut type_params = Vec::new();
+    let mut type_names = Vec::new();
+    for param in params.into_iter().filter(|param| !param.is_empty()) {
+        // Drop a default (`= ...`) at the top level.
+        let mut angles = Angles::default();
+        let end = param
+            .iter()
+            .position(|token| {
+                angles.update(token);
+                angles.depth == 0 && matches!(token, TokenTree::Punct(punct) if punct.as_char() == '=')
+            })
+            .unwrap_or(param.len());
+        let param = &param[..end];
+        impl_params.push(join(param));
+
+        let name = match param {
+            [TokenTree::Punct(quote), TokenTree::Ident(lifetime), ..] if quote.as_char() == '\'' => {
+                format!("'{lifetime}")
+            }
+            [TokenTree::Ident(keyword), TokenTree::Ident(name), ..] if keyword.to_string() == "const" => {
+                name.to_string()
+            }
+            [TokenTree::Ident(name), ..] => {
+                type_names.push(name.to_string());
+                name.to_string()
+            }
+            _ => return Err(("unsupported generic parameter".into(), Span::call_site())),
+        };
+        type_params.push(name);
+    }
+
+    Ok((
+        format!("<{}>", impl_params.join(", ")),
+        format!("<{}>", type_params.join(", ")),
+        type_names,
+    ))
+}
+
+/// Parses named (`{ ... }`) or tuple (`( ... )`) fields with their `#[cans(...)]` options.
+fn parse_fields(stream: TokenStream, named: bool) -> Result<Vec<Field>, Error> {
+    let mut fields = Vec::new();
+    let mut current: Vec<TokenTree> = Vec::new();
+    let mut angles = Angles::default();
+    for token in stream {
+        if angles.depth == 0 && matches!(&token, TokenTree::Punct(punct) if punct.as_char() == ',') {
+            if !current.is_empty() {
+                fields.push(parse_field(std::mem::take(&mut current), named, fields.len())?);
+            }
+            continue;
+        }
+        angles.update(&token);
+        current.push(token);
+    }
+    if !current.is_empty() {
+        fields.push(parse_field(current, named, fields.len())?);
+    }
+    Ok(fields)
+}
+
+fn parse_field(tokens: Vec<TokenTree>, named: bool, index: usize) -> Result<Field, Error> {
+    let mut position = 0;
+    let mut rename = None;
+    let mut skip = false;
+    let mut flatten = false;
+
+    while is_punct(tokens.get(position), '#') {
+        if let Some(TokenTree::Group(attribute)) = tokens.get(position + 1) {
+            parse_attribute(attribute, &mut rename, &mut skip, &mut flatten)?;
+        }
+        position += 2;
+    }
+    skip_visibility(&tokens, &mut position);
+
+    let member = if named {
+        let Some(TokenTree::Ident(name)) = tokens.get(position) else {
+            return Err(("expected a field name".into(), Span::call_site()));
+        };
+        position += 2; // name and `:`
+        name.to_string()
+    } else {
+        index.to_string()
+    };
+
+    let key = rename.unwrap_or_else(|| member.trim_start_matches("r#").to_string());
+    let ty = join(&tokens[position..]);
+    Ok(Field { member, ty, key, skip, flatten })
+}
+
+/// Reads `#[cans(crate = "...")]` on the struct itself.
+fn parse_container_attribute(attribute: &Group, krate: &mut String) -> Result<(), Error> {
+    let Some(options) = cans_options(attribute) else {
+        return Ok(());
+    };
+    let mut position = 0;
+    while let Some(token) = options.get(position) {
+        match token {
+            TokenTree::Ident(option) if option.to_string() == "crate" => {
+                let path = string_option(&options, &mut position, option, "crate = \"path\"")?;
+                if path.is_empty() {
+                    return Err(("expected a path to the `cans` crate".into(), option.span()));
+                }
+                *krate = path;
+            }
+            TokenTree::Punct(comma) if comma.as_char() == ',' => {}
+            token => {
+                return Err(("unknown `cans` container option; expected `crate = \"...\"`".into(), token.span()));
+            }
+        }
+        position += 1;
+    }
+    Ok(())
+}
+
+/// Reads `#[cans(rename = "...")]`, `#[cans(skip)]` and `#[cans(flatten)]`.
+fn parse_attribute(
+    attribute: &Group,
+    rename: &mut Option<String>,
+    skip: &mut bool,
+    flatten: &mut bool,
+) -> Result<(), Error> {
+    let Some(options) = cans_options(attribute) else {
+        return Ok(());
+    };
+    let mut position = 0;
+    while let Some(token) = options.get(position) {
+        match token {
+            TokenTree::Ident(option) if option.to_string() == "skip" => *skip = true,
+            TokenTree::Ident(option) if option.to_string() == "flatten" => *flatten = true,
+            TokenTree::Ident(option) if option.to_string() == "rename" => {
+                *rename = Some(string_option(&options, &mut position, option, "rename = \"key\"")?);
+            }
+            TokenTree::Punct(comma) if comma.as_char() == ',' => {}
+            token => {
+                return Err((
+                    "unknown `cans` option; expected `rename = \"...\"`, `skip` or `flatten`".into(),
+                    token.span(),
+                ));
+            }
+        }
+        position += 1;
+    }
+    Ok(())
+}
+
+/// Returns the options inside `#[cans(...)]`, or `None` for any other attribute.
+fn cans_options(attribute: &Group) -> Option<Vec<TokenTree>> {
+    let tokens: Vec<TokenTree> = attribute.stream().into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Ident(path), TokenTree::Group(options)] if path.to_string() == "cans" => {
+            Some(options.stream().into_iter().collect())
+        }
+        _ => None,
+    }
+}
+
+/// Reads the string after `option =`, leaving `position` on the string.
+fn string_option(options: &[TokenTree], position: &mut usize, option: &Ident, form: &str) -> Result<String, Error> {
+    match (options.get(*position + 1), options.get(*position + 2)) {
+        (Some(TokenTree::Punct(eq)), Some(TokenTree::Literal(value))) if eq.as_char() == '=' => {
+            let value = value.to_string();
+            let Some(value) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+                return Err((format!("expected a string: `{form}`"), option.span()));
+            };
+            *position += 2;
+            Ok(value.to_string())
+        }
+        _ => Err((format!("expected `{form}`"), option.span())),
+    }
+}
+
+fn join(tokens: &[TokenTree]) -> String {
+    tokens.iter().cloned().collect::<TokenStream>().to_string()
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use proc_macro::{Delimiter, Group, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+use crate::{compile_error, tokens};
+
+/// Expands `embed_templates!("dir")` to `&[("name", include_str!("/abs/dir/name")), ...]`.
+pub(crate) fn embed_templates(input: TokenStream) -> TokenStream {
+    let mut tokens_in: Vec<TokenTree> = input.into_iter().collect();
+    while let [TokenTree::Group(group)] = tokens_in.as_slice() {
+        if group.delimiter() != Delimiter::None {
+            break;
+        }
+        tokens_in = group.stream().into_iter().collect();
+    }
+    let (dir, span) = match tokens_in.as_slice() {
+        [TokenTree::Literal(literal)] => match string_value(&literal.to_string()) {
+            Some(dir) => (dir, literal.span()),
+            None => return compile_error("expected a directory path such as `\"templates\"`", literal.span()),
+        },
+        _ => return compile_error("expected a directory path such as `\"templates\"`", Span::call_site()),
+    };
+
+    let root = match env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest) => Path::new(&manifest).join(&dir),
+        Err(_) => PathBuf::from(&dir),
+    };
+    let mut files = Vec::new();
+    if let Err(error) = collect_files(&root, "", &mut files) {
+        return compile_error(&format!("cannot read the template directory `{}`: {error}", root.display()), span);
+    }
+    files.sort();
+
+    let mut entries = TokenStream::new();
+    for (name, path) in files {
+        let mut include = tokens("include_str!");
+        include.extend([TokenTree::Group(Group::new(
+            Delimiter::Parenthesis,
+            TokenTree::Literal(Literal::string(&path.to_string_lossy())).into(),
+        ))]);
+        let mut entry = TokenStream::from(TokenTree::Literal(Literal::string(&name)));
+        entry.extend(tokens(","));
+        entry.extend(include);
+        entries.extend([
+            TokenTree::Group(Group::new(Delimiter::Parenthesis, entry)),
+            TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+        ]);
+    }
+    let mut output = tokens("&");
+    output.extend([TokenTree::Group(Group::new(Delimiter::Bracket, entries))]);
+    output
+}
+
+/// Lists the files below `dir` with their names relative to the template directory, skipping
+/// hidden files and directories.
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let name = format!("{prefix}{file_name}");
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(&path, &format!("{name}/"), files)?;
+        } else {
+            files.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the value of a plain or raw string literal without escape sequences.
+fn string_value(literal: &str) -> Option<String> {
+    let raw = literal.strip_prefix('r').map(|rest| rest.trim_matches('#'));
+    let quoted = raw.unwrap_or(literal);
+    let value = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    if raw.is_none() && value.contains('\\') {
+        return None;
+    }
+    Some(value.to_string())
+}
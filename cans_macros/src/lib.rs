@@ -0,0 +1,265 @@
+//! Procedural macros for the [`cans`](https://crates.io/crates/cans) template engine.
+//!
+//! This crate is an implementation detail of `cans` and is re-exported from it;
+//! depend on `cans` instead of using it directly.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+mod derive;
+mod embed;
+
+/// ### #[derive(Context)]
+///
+/// Context Derive Macro
+///
+/// Implements `cans::content::Context` for a struct so that every field is available as a
+/// placeholder named after the field, and `cans::content::TemplateValue` so that the struct can
+/// be nested inside another context. Field values must implement `TemplateValue`.
+///
+/// Field options:
+/// - `#[cans(rename = "key")]`: expose the field under another placeholder name.
+/// - `#[cans(skip)]`: do not expose the field.
+/// - `#[cans(flatten)]`: expose the fields of a nested context as if they were declared here.
+///
+/// Tuple struct fields are exposed as `0`, `1`, and so on.
+///
+/// Container options:
+/// - `#[cans(crate = "path")]`: refer to the `cans` crate through `path` instead of `::cans`,
+///   for crates that depend on it under another name or reach it through a re-export.
+///
+/// <small>End Doc</small>
+#[proc_macro_derive(Context, attributes(cans))]
+pub fn derive_context(input: TokenStream) -> TokenStream {
+    derive::derive_context(input)
+}
+
+/// ### embed_templates!($dir)
+///
+/// Template Embedding Macro
+///
+/// Expands to a `&'static [(&'static str, &'static str)]` slice that pairs the name of every file
+/// below `$dir` with its contents, embedded with `include_str!`. The directory is relative to
+/// the crate root (`CARGO_MANIFEST_DIR`), names are paths relative to the directory with `/`
+/// separators, such as `layouts/base.html`, and hidden files are skipped. The directory is read
+/// when the crate is compiled: edits to embedded files trigger a rebuild, while added files are
+/// only picked up by the next build of the calling crate.
+///
+/// <small>End Doc</small>
+#[proc_macro]
+pub fn embed_templates(input: TokenStream) -> TokenStream {
+    embed::embed_templates(input)
+}
+
+/// ### check_template!($crate; $content; $key, ...; $open, $close [; ..])
+///
+/// Compile-Time Template Check
+///
+/// Called by `do_replace!`, `do_html!`, `do_xml!` and `do_json!` with the template expression,
+/// the argument keys and the tag delimiters. When the template and both delimiters are string
+/// literals, `include_str!` invocations or `const`s (paths whose last segment is written in
+/// `SCREAMING_CASE`), it expands to `const` items that scan the template at compile time and
+/// fails the build when:
+///
+/// - a `{{placeholder}}` in the template has no matching argument (reported on the template;
+///   skipped when a trailing `; ..` marks a spread context that may supply any key), or
+/// - an argument matches no placeholder in the template (reported on the argument key).
+///
+/// Duplicate argument keys are reported directly. Any other template expression is only known
+/// at run time and expands to nothing.
+///
+/// <small>End Doc</small>
+#[proc_macro]
+pub fn check_template(input: TokenStream) -> TokenStream {
+    let mut sections = split_sections(input).into_iter();
+    let (Some(krate), Some(content), Some(keys), Some(delimiters)) =
+        (sections.next(), sections.next(), sections.next(), sections.next())
+    else {
+        return compile_error("expected `$crate; content; key, ...; open, close`", Span::call_site());
+    };
+
+    let keys: Vec<Ident> = keys
+        .into_iter()
+        .filter_map(|token| match token {
+            TokenTree::Ident(ident) => Some(ident),
+            _ => None,
+        })
+        .collect();
+
+    let mut output = TokenStream::new();
+    for (index, key) in keys.iter().enumerate() {
+        if keys[..index].iter().any(|other| other.to_string() == key.to_string()) {
+            output.extend(compile_error(&format!("duplicate argument `{key}`"), key.span()));
+        }
+    }
+
+    // A spread context (`..context`) may supply any placeholder.
+    let spread = sections.next().is_some();
+
+    let content = unwrap_none_groups(content);
+    let delimiters: Vec<TokenStream> = split_on(delimiters, ',').into_iter().map(unwrap_none_groups).collect();
+    if !is_constant_template(&content) || delimiters.len() != 2 || !delimiters.iter().all(is_constant_template) {
+        return output;
+    }
+
+    let content_span = content.clone().into_iter().last().map_or(Span::call_site(), |token| token.span());
+
+    // The checks take the template followed by its delimiters.
+    let mut content = content;
+    for delimiter in delimiters {
+        content.extend(tokens(","));
+        content.extend(delimiter);
+    }
+
+    // Every placeholder must be supplied; reported on the template expression.
+    if !spread {
+        output.extend(check_supplied(&krate, &content, &keys, content_span));
+    }
+
+    // Every argument must match a placeholder; reported on the argument key.
+    for key in &keys {
+        let name = TokenStream::from(TokenTree::Literal(Literal::string(&key.to_string())));
+        output.extend(check_call(&krate, "check_used", &content, name, key.span()));
+    }
+    output
+}
+
+fn check_supplied(krate: &TokenStream, content: &TokenStream, keys: &[Ident], span: Span) -> TokenStream {
+    let mut key_list = TokenStream::new();
+    for key in keys {
+        key_list.extend([
+            TokenTree::Literal(Literal::string(&key.to_string())),
+            TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+        ]);
+    }
+    let mut slice = TokenStream::new();
+    slice.extend([
+        TokenTree::Punct(Punct::new('&', Spacing::Alone)),
+        TokenTree::Group(Group::new(Delimiter::Bracket, key_list)),
+    ]);
+    check_call(krate, "check_supplied", content, slice, span)
+}
+
+/// Splits the macro input on top-level `;` separators.
+fn split_sections(input: TokenStream) -> Vec<TokenStream> {
+    split_on(input, ';')
+}
+
+/// Splits `input` on top-level `separator` punctuation.
+fn split_on(input: TokenStream, separator: char) -> Vec<TokenStream> {
+    let mut sections = vec![TokenStream::new()];
+    for token in input {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == separator => sections.push(TokenStream::new()),
+            _ => sections.last_mut().unwrap().extend([token]),
+        }
+    }
+    sections
+}
+
+/// Looks through the invisible groups that `macro_rules!` puts around `$content:expr`.
+fn unwrap_none_groups(stream: TokenStream) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = stream.into_iter().collect();
+    while let [TokenTree::Group(group)] = tokens.as_slice() {
+        if group.delimiter() != Delimiter::None {
+            break;
+        }
+        tokens = group.stream().into_iter().collect();
+    }
+    tokens.into_iter().collect()
+}
+
+/// Returns `true` for a string literal, an `include_str!(..)` invocation or a path ending in a
+/// `SCREAMING_CASE` constant.
+fn is_constant_template(content: &TokenStream) -> bool {
+    let tokens: Vec<TokenTree> = content.clone().into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Ident(name), TokenTree::Punct(bang), TokenTree::Group(group)] => {
+            name.to_string() == "include_str" && bang.as_char() == '!' && group.delimiter() == Delimiter::Parenthesis
+        }
+        [TokenTree::Literal(literal)] => {
+            let literal = literal.to_string();
+            literal.starts_with('"') || literal.starts_with("r\"") || literal.starts_with("r#")
+        }
+        [.., TokenTree::Ident(last)] => {
+            let path_like = tokens.iter().all(|token| match token {
+                TokenTree::Ident(_) => true,
+                TokenTree::Punct(punct) => punct.as_char() == ':',
+                _ => false,
+            });
+            // `Self::TEMPLATE` cannot be named from the generated `const` item.
+            let self_path = matches!(tokens.first(), Some(TokenTree::Ident(first)) if first.to_string() == "Self");
+            let name = last.to_string();
+            path_like
+                && !self_path
+                && name.bytes().any(|b| b.is_ascii_uppercase())
+                && name.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+        }
+        _ => false,
+    }
+}
+
+/// Builds `const _: () = { use $crate::rules as rules; rules::__<check>(content, open, close, argument) };`,
+/// where `content` carries the template and its delimiters.
+///
+/// `$crate` and the template tokens keep their own spans so that they still resolve, while the
+/// call itself is placed at `span` so that a failing check is reported there.
+fn check_call(
+    krate: &TokenStream,
+    check: &str,
+    content: &TokenStream,
+    argument: TokenStream,
+    span: Span,
+) -> TokenStream {
+    let mut arguments = content.clone();
+    arguments.extend(respan(tokens(","), span));
+    arguments.extend(respan(argument, span));
+    let mut arguments = Group::new(Delimiter::Parenthesis, arguments);
+    arguments.set_span(span);
+
+    let mut body = tokens("use");
+    body.extend(krate.clone());
+    body.extend(respan(tokens("::rules as __cans_rules;"), span));
+    body.extend(respan(tokens(&format!("__cans_rules::__{check}")), span));
+    body.extend([TokenTree::Group(arguments)]);
+
+    let mut item = tokens("const _: () =");
+    item.extend([TokenTree::Group(Group::new(Delimiter::Brace, body))]);
+    item.extend(tokens(";"));
+    item
+}
+
+fn respan(stream: TokenStream, span: Span) -> TokenStream {
+    stream
+        .into_iter()
+        .map(|mut token| {
+            if let TokenTree::Group(group) = &token {
+                let mut respanned = Group::new(group.delimiter(), respan(group.stream(), span));
+                respanned.set_span(span);
+                token = TokenTree::Group(respanned);
+            } else {
+                token.set_span(span);
+            }
+            token
+        })
+        .collect()
+}
+
+pub(crate) fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    respan(tokens("::core::compile_error!"), span)
+        .into_iter()
+        .chain([
+            TokenTree::Group(Group::new(Delimiter::Parenthesis, TokenTree::Literal(message).into())),
+            TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+        ])
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
+
+pub(crate) fn tokens(source: &str) -> TokenStream {
+    source.parse().expect("valid tokens")
+}
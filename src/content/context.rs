@@ -0,0 +1,173 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+use super::value::{TemplateValue, Value};
+
+/// ### Context
+///
+/// Template Context Trait
+///
+/// A `Context` supplies the values for the placeholders of a template. `get` is called with
+/// the placeholder name and returns `None` when the context has no value for it, in which case
+/// the placeholder is left in the output unchanged.
+///
+/// `Context` is implemented for slices, arrays and vectors of `(key, value)` pairs (the first
+/// pair with a matching key wins), for `HashMap` and `BTreeMap` with string keys, and for
+/// closures wrapped with `from_fn`. Implement it for your own types to look values up
+/// anywhere, such as a CMS or a configuration file.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{Context, Template, Value};
+///
+/// let context = [("name", "Ahmed"), ("city", "Khartoum")];
+/// assert_eq!(Context::get(&context, "name"), Some(Value::from("Ahmed")));
+/// assert_eq!(Context::get(&context, "country"), None);
+///
+/// let template = Template::new("{{name}} from {{city}}");
+/// assert_eq!(template.render(&context), "Ahmed from Khartoum");
+/// ```
+///
+/// #### Example: Deriving `Context` for a view-model
+///
+/// `#[cans(rename = "...")]` exposes a field under another name, `#[cans(skip)]` hides it and
+/// `#[cans(flatten)]` exposes the fields of a nested context directly. The generated code refers
+/// to `::cans`; a crate that depends on cans under another name, or reaches it through a
+/// re-export, sets the path with `#[cans(crate = "path")]` on the struct.
+/// ```rust
+/// use cans::do_html;
+/// use cans::content::Template;
+///
+/// #[derive(cans::Context)]
+/// struct Seo {
+///     description: String,
+/// }
+///
+/// #[derive(cans::Context)]
+/// struct HomePage {
+///     #[cans(rename = "page_title")]
+///     title: String,
+///     visits: u64,
+///     #[cans(skip)]
+///     session_token: String,
+///     #[cans(flatten)]
+///     seo: Seo,
+/// }
+///
+/// let page = HomePage {
+///     title: "Home".into(),
+///     visits: 42,
+///     session_token: "secret".into(),
+///     seo: Seo { description: "CANS & Rust".into() },
+/// };
+///
+/// let template = "<title>{{page_title}}</title><meta content=\"{{description}}\">{{visits}}";
+/// assert_eq!(
+///     do_html!(template, ..page),
+///     "<title>Home</title><meta content=\"CANS &amp; Rust\">42"
+/// );
+/// assert_eq!(Template::new("{{session_token}}").render(&page), "{{session_token}}");
+///
+/// // Explicit arguments take precedence over the spread context.
+/// assert_eq!(do_html!("<title>{{page_title}}</title>", page_title = "About", ..page), "<title>About</title>");
+/// ```
+/// <small>End Doc</small>
+pub trait Context {
+    /// Returns the value for the placeholder `key`, or `None` when there is none.
+    fn get(&self, key: &str) -> Option<Value<'_>>;
+}
+
+impl<C: Context + ?Sized> Context for &C {
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        (**self).get(key)
+    }
+}
+
+impl<K: AsRef<str>, V: TemplateValue> Context for [(K, V)] {
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        self.iter()
+            .find(|(name, _)| name.as_ref() == key)
+            .map(|(_, value)| value.to_value())
+    }
+}
+
+impl<K: AsRef<str>, V: TemplateValue, const N: usize> Context for [(K, V); N] {
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        Context::get(self.as_slice(), key)
+    }
+}
+
+impl<K: AsRef<str>, V: TemplateValue> Context for Vec<(K, V)> {
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        Context::get(self.as_slice(), key)
+    }
+}
+
+impl<K, V, S> Context for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    V: TemplateValue,
+    S: BuildHasher,
+{
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        HashMap::get(self, key).map(TemplateValue::to_value)
+    }
+}
+
+impl<K, V> Context for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: TemplateValue,
+{
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        BTreeMap::get(self, key).map(TemplateValue::to_value)
+    }
+}
+
+/// ### FromFn
+///
+/// Closure Context
+///
+/// A `Context` that looks values up by calling a closure. Create it with `from_fn`.
+///
+/// <small>End Doc</small>
+#[derive(Clone, Copy)]
+pub struct FromFn<F>(F);
+
+impl<F, V> Context for FromFn<F>
+where
+    F: Fn(&str) -> Option<V>,
+    V: TemplateValue,
+{
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        (self.0)(key).and_then(|value| value.to_value().into_owned())
+    }
+}
+
+/// ### from_fn(f)
+///
+/// Closure Context Function
+///
+/// The `from_fn` function turns a closure into a `Context`. The closure is called with each
+/// placeholder name and returns `Some(value)` to fill it, or `None` to leave it unresolved.
+///
+/// ### Parameters
+/// - `f`: A closure `Fn(&str) -> Option<V>`, where `V` implements `TemplateValue`.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{from_fn, render_with};
+///
+/// let context = from_fn(|key: &str| key.strip_prefix("upper_").map(str::to_uppercase));
+/// let result = render_with("{{upper_cans}} {{other}}", &context);
+/// assert_eq!(result, "CANS {{other}}");
+/// ```
+/// <small>End Fun Doc</small>
+pub fn from_fn<F, V>(f: F) -> FromFn<F>
+where
+    F: Fn(&str) -> Option<V>,
+    V: TemplateValue,
+{
+    FromFn(f)
+}
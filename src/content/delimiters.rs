@@ -0,0 +1,74 @@
+use super::parser::Delims;
+
+/// ### Delimiters
+///
+/// Tag Delimiters
+///
+/// `Delimiters` set the opening and closing markers of the tags of a template, `{{` and `}}`
+/// by default. Other delimiters, such as `[[` and `]]` or `<%=` and `%>`, keep templates for
+/// markup that uses `{{ }}` itself, such as Vue, Alpine, Go or Jinja templates, intact: only
+/// tags written with the configured delimiters are filled, and everything else is left as it
+/// is. Every tag uses the same delimiters, as in `[[#if x]]...[[/if]]` or `[[> card]]`.
+///
+/// In any template, a literal tag holding a quoted string, as in `{{"{{"}}`, writes the string as
+/// it is, so `{{"{{"}}name}}` is written as `{{name}}`. With other delimiters the literal uses
+/// them too, as in `[["[["]]`. Backslashes have no special meaning, so a Windows path such as
+/// `C:\{{dir}}` is written with the backslash followed by the directory.
+///
+/// Delimiters are set on a `Template` with `with_delimiters`, on a `TemplateRegistry` for the
+/// templates registered afterwards, and on the content macros with the `@delimiters(open, close)`
+/// form.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{Delimiters, Template};
+/// use cans::do_html;
+///
+/// let vue = Template::new("<p>{{ message }} [[user]]</p>").with_delimiters(Delimiters::new("[[", "]]"));
+/// assert_eq!(vue.render(&[("user", "Amal")]), "<p>{{ message }} Amal</p>");
+///
+/// let erb = do_html!(@delimiters("<%=", "%>"), "<h1><%=title%></h1>", title = "A & B");
+/// assert_eq!(erb, "<h1>A &amp; B</h1>");
+///
+/// assert_eq!(do_html!(r#"{{"{{"}}name}} is {{name}}"#, name = "Ahmed"), "{{name}} is Ahmed");
+/// assert_eq!(do_html!(r"C:\{{dir}}", dir = "Users"), r"C:\Users");
+/// ```
+///
+/// ### Panics
+/// `Delimiters::new` panics if either delimiter is empty.
+///
+/// <small>End Doc</small>
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Delimiters {
+    open: String,
+    close: String,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Delimiters::new(Delims::DEFAULT.open, Delims::DEFAULT.close)
+    }
+}
+
+impl Delimiters {
+    /// Creates delimiters that open tags with `open` and close them with `close`.
+    pub fn new(open: impl Into<String>, close: impl Into<String>) -> Self {
+        let (open, close) = (open.into(), close.into());
+        assert!(!open.is_empty() && !close.is_empty(), "template delimiters must not be empty");
+        Delimiters { open, close }
+    }
+
+    /// Returns the opening delimiter.
+    pub fn open(&self) -> &str {
+        &self.open
+    }
+
+    /// Returns the closing delimiter.
+    pub fn close(&self) -> &str {
+        &self.close
+    }
+
+    pub(crate) fn delims(&self) -> Delims<'_> {
+        Delims { open: &self.open, close: &self.close }
+    }
+}
@@ -0,0 +1,236 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use super::parser::line_column;
+
+/// ### RenderError
+///
+/// Strict Rendering Error
+///
+/// A `RenderError` is returned by `Template::render_strict`,
+/// `TemplateRegistry::render_strict` and the `try_do_*` macros when a template cannot be
+/// rendered completely: a placeholder has no value, a tag is malformed, a section tag is
+/// misplaced or never closed, a partial cannot be included, or an argument matches no
+/// placeholder. It lists every problem found in the template, in source order and with its
+/// byte offsets, line and column, so that all of them can be fixed at once.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{IssueKind, Template};
+///
+/// let template = Template::new("{{name}} lives in {{address.city}}");
+/// let error = template.render_strict(&[("city", "Khartoum")]).unwrap_err();
+///
+/// assert_eq!(error.issues().len(), 2);
+/// assert_eq!(error.issues()[0].span, 0..8);
+/// assert_eq!((error.issues()[1].line, error.issues()[1].column), (1, 19));
+/// assert!(matches!(error.issues()[0].kind, IssueKind::Unresolved { .. }));
+/// assert_eq!(
+///     error.to_string(),
+///     "template could not be rendered:\n\
+///      - `{{name}}` (line 1, column 1, bytes 0..8): the placeholder has no value\n\
+///      - `{{address.city}}` (line 1, column 19, bytes 18..34): the placeholder has no value"
+/// );
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderError {
+    issues: Vec<Issue>,
+}
+
+impl RenderError {
+    pub(crate) fn new(issues: Vec<Issue>) -> Self {
+        RenderError { issues }
+    }
+
+    /// Returns the problems found in the template, in source order.
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("template could not be rendered:")?;
+        for issue in &self.issues {
+            write!(f, "\n- {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RenderError {}
+
+/// A single problem found while rendering a template in strict mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// What went wrong.
+    pub kind: IssueKind,
+    /// The byte range of the offending tag in the template source.
+    pub span: Range<usize>,
+    /// The line of the start of `span`, counting from 1.
+    pub line: usize,
+    /// The column of the start of `span` in characters, counting from 1.
+    pub column: usize,
+    /// The name of the registered template that contains the tag, or `None` for a template
+    /// rendered on its own.
+    pub template: Option<String>,
+}
+
+impl Issue {
+    /// Creates an issue about the tag at `span` of `source`.
+    pub(crate) fn new(kind: IssueKind, span: Range<usize>, source: &str) -> Self {
+        let (line, column) = line_column(source, span.start);
+        Issue { kind, span, line, column, template: None }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(tag) = self.kind.tag() {
+            write!(f, "`{tag}`")?;
+            if let Some(template) = &self.template {
+                write!(f, " in `{template}`")?;
+            }
+            write!(f, " (line {}, column {}, bytes {:?}): ", self.line, self.column, self.span)?;
+        }
+
+        match &self.kind {
+            IssueKind::Unresolved { .. } => f.write_str("the placeholder has no value"),
+            IssueKind::MissingRequired { .. } => f.write_str("the placeholder is required but has no value"),
+            IssueKind::MissingSegment { path, segment, .. } => write!(f, "`{path}` has no `{segment}`"),
+            IssueKind::MalformedTag { .. } => f.write_str("the tag is not a valid placeholder or section tag"),
+            IssueKind::UnexpectedTag { .. } => f.write_str("the tag does not close or continue an open section"),
+            IssueKind::UnclosedSection { .. } => f.write_str("the section is never closed"),
+            IssueKind::UnusedArgument { key } => write!(f, "argument `{key}` matches no placeholder in the template"),
+            IssueKind::UnknownPartial { name, .. } | IssueKind::UnknownTemplate { name } => {
+                write!(f, "no template named `{name}` is registered")
+            }
+            IssueKind::PartialCycle { name, .. } => write!(f, "`{name}` includes or extends itself"),
+            IssueKind::DepthLimit { limit, .. } => write!(f, "partials are nested more than {limit} levels deep"),
+            IssueKind::UnknownFilter { name, .. } => write!(f, "no filter named `{name}` exists"),
+            IssueKind::FilterFailed { filter, message, .. } => write!(f, "the `{filter}` filter failed: {message}"),
+        }
+    }
+}
+
+/// The kind of an `Issue`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IssueKind {
+    /// The context has no value for the placeholder `tag`.
+    Unresolved {
+        /// The tag as written in the template, such as `{{name}}`.
+        tag: String,
+    },
+    /// The context has no value, or a null value such as `None`, for the required placeholder
+    /// `tag`, such as `{{title!}}`. The tag is written as literal text.
+    MissingRequired {
+        /// The tag as written in the template.
+        tag: String,
+    },
+    /// A dotted path resolved up to `path`, whose value has no entry `segment`.
+    MissingSegment {
+        /// The tag as written in the template, such as `{{user.address.city}}`.
+        tag: String,
+        /// The part of the path that resolved, such as `user.address`.
+        path: String,
+        /// The first segment that did not resolve, such as `city`.
+        segment: String,
+    },
+    /// Text that opens with `{{` but is not a valid tag, such as `{{ name }}`, `{{name` or
+    /// `{{#if}}`. The text is written unchanged.
+    MalformedTag {
+        /// The text from the `{{` up to and including the next `}}`, or to the end of the
+        /// line when the tag is never closed.
+        tag: String,
+    },
+    /// An `{{else}}` or closing tag such as `{{/if}}` outside of a matching section, a
+    /// `{{super()}}` outside of a block, or an `{{extends}}` that is not the first one at the top
+    /// level of the template. The tag is written as literal text.
+    UnexpectedTag {
+        /// The tag as written in the template.
+        tag: String,
+    },
+    /// An `{{#if}}`, `{{#unless}}`, `{{#each}}` or `{{#block}}` section without a closing tag. The opening tag is written
+    /// as literal text and the section contents are rendered unconditionally.
+    UnclosedSection {
+        /// The opening tag as written in the template.
+        tag: String,
+    },
+    /// A `{{> name}}` include or an `{{extends "name"}}` layout that is not registered, or that is
+    /// rendered without a `TemplateRegistry`. The tag is written as literal text.
+    UnknownPartial {
+        /// The tag as written in the template.
+        tag: String,
+        /// The name of the missing template.
+        name: String,
+    },
+    /// A `{{> name}}` include or an `{{extends "name"}}` layout of a template that is already
+    /// being rendered further up, which would recurse forever. The tag is written as literal text.
+    PartialCycle {
+        /// The tag as written in the template.
+        tag: String,
+        /// The name of the included or extended template.
+        name: String,
+    },
+    /// A `{{> name}}` include nested deeper than the registry's depth limit. The tag is written
+    /// as literal text.
+    DepthLimit {
+        /// The tag as written in the template.
+        tag: String,
+        /// The maximum include depth.
+        limit: usize,
+    },
+    /// A `{{path|name}}` filter that is neither built in nor registered on the
+    /// `TemplateRegistry`. The tag is written as literal text.
+    UnknownFilter {
+        /// The tag as written in the template.
+        tag: String,
+        /// The name of the missing filter.
+        name: String,
+    },
+    /// A filter could not be applied to the value of a placeholder, such as `round` applied to
+    /// text that is not a number. The tag is written as literal text.
+    FilterFailed {
+        /// The tag as written in the template.
+        tag: String,
+        /// The name of the filter.
+        filter: String,
+        /// Why the filter failed.
+        message: String,
+    },
+    /// An argument passed to a `try_do_*` macro that no placeholder, section or partial
+    /// argument of the template refers to. The issue has an empty span.
+    UnusedArgument {
+        /// The key of the argument.
+        key: String,
+    },
+    /// `TemplateRegistry::render_strict` was asked for a template that is not registered.
+    /// The issue has an empty span.
+    UnknownTemplate {
+        /// The requested name.
+        name: String,
+    },
+}
+
+impl IssueKind {
+    /// Returns the tag the issue is about, as written in the template.
+    pub fn tag(&self) -> Option<&str> {
+        match self {
+            IssueKind::Unresolved { tag }
+            | IssueKind::MissingRequired { tag }
+            | IssueKind::MissingSegment { tag, .. }
+            | IssueKind::MalformedTag { tag }
+            | IssueKind::UnexpectedTag { tag }
+            | IssueKind::UnclosedSection { tag }
+            | IssueKind::UnknownPartial { tag, .. }
+            | IssueKind::PartialCycle { tag, .. }
+            | IssueKind::DepthLimit { tag, .. }
+            | IssueKind::UnknownFilter { tag, .. }
+            | IssueKind::FilterFailed { tag, .. } => Some(tag),
+            IssueKind::UnusedArgument { .. } | IssueKind::UnknownTemplate { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+
+/// ### Escape
+///
+/// Output Escaping Modes
+///
+/// The `Escape` enum selects how substituted values are escaped before they are written
+/// into a template. `do_replace!` uses `Escape::None`, while `do_html!`, `do_xml!` and
+/// `do_json!` use `Escape::Html`, `Escape::Xml` and `Escape::Json` respectively.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::Escape;
+///
+/// assert_eq!(Escape::Html.apply("<b>Tom & Jerry</b>"), "&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;");
+/// assert_eq!(Escape::Json.apply("say \"hi\""), r#"say \"hi\""#);
+/// assert_eq!(Escape::None.apply("<b>"), "<b>");
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Escape {
+    /// Values are inserted unchanged.
+    #[default]
+    None,
+    /// Values are escaped as HTML text and attribute content.
+    Html,
+    /// Values are escaped using the five predefined XML entities.
+    Xml,
+    /// Values are escaped as the content of a JSON string.
+    Json,
+}
+
+impl Escape {
+    /// Escapes `value` for this mode, borrowing it when nothing needs to change.
+    pub fn apply(self, value: &str) -> Cow<'_, str> {
+        match self {
+            Escape::None => Cow::Borrowed(value),
+            Escape::Html => escape_with(value, html_entity),
+            Escape::Xml => escape_with(value, xml_entity),
+            Escape::Json => escape_json_cow(value),
+        }
+    }
+}
+
+fn html_entity(c: char) -> Option<&'static str> {
+    match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        '"' => Some("&quot;"),
+        '\'' => Some("&#x27;"),
+        _ => None,
+    }
+}
+
+fn xml_entity(c: char) -> Option<&'static str> {
+    match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        '"' => Some("&quot;"),
+        '\'' => Some("&apos;"),
+        _ => None,
+    }
+}
+
+fn escape_with(value: &str, entity: fn(char) -> Option<&'static str>) -> Cow<'_, str> {
+    let Some(first) = value.find(|c| entity(c).is_some()) else {
+        return Cow::Borrowed(value);
+    };
+
+    let mut escaped = String::with_capacity(value.len() + 16);
+    escaped.push_str(&value[..first]);
+    for c in value[first..].chars() {
+        match entity(c) {
+            Some(replacement) => escaped.push_str(replacement),
+            None => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+fn escape_json_cow(value: &str) -> Cow<'_, str> {
+    let needs_escape = |c: char| c == '"' || c == '\\' || c < '\u{20}';
+    let Some(first) = value.find(needs_escape) else {
+        return Cow::Borrowed(value);
+    };
+
+    let mut escaped = String::with_capacity(value.len() + 16);
+    escaped.push_str(&value[..first]);
+    for c in value[first..].chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{08}' => escaped.push_str("\\b"),
+            '\u{0C}' => escaped.push_str("\\f"),
+            c if c < '\u{20}' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// ### escape_html(value)
+///
+/// HTML Escaping Function
+///
+/// The `escape_html` function replaces the characters `&`, `<`, `>`, `"` and `'` with their
+/// HTML entities, making the value safe to place inside HTML text or quoted attributes.
+///
+/// ### Parameters
+/// - `value`: A string slice (`&str`) to escape.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::escape_html;
+///
+/// let escaped = escape_html(r#"<script>alert("x")</script>"#);
+/// assert_eq!(escaped, "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt;");
+/// ```
+/// <small>End Fun Doc</small>
+pub fn escape_html(value: &str) -> String {
+    Escape::Html.apply(value).into_owned()
+}
+
+/// ### escape_xml(value)
+///
+/// XML Escaping Function
+///
+/// The `escape_xml` function replaces the characters `&`, `<`, `>`, `"` and `'` with the
+/// predefined XML entities (`&amp;`, `&lt;`, `&gt;`, `&quot;` and `&apos;`).
+///
+/// ### Parameters
+/// - `value`: A string slice (`&str`) to escape.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::escape_xml;
+///
+/// let escaped = escape_xml("Tom's <note> & more");
+/// assert_eq!(escaped, "Tom&apos;s &lt;note&gt; &amp; more");
+/// ```
+/// <small>End Fun Doc</small>
+pub fn escape_xml(value: &str) -> String {
+    Escape::Xml.apply(value).into_owned()
+}
+
+/// ### escape_json(value)
+///
+/// JSON String Escaping Function
+///
+/// The `escape_json` function escapes a value so that it can be placed between the quotes
+/// of a JSON string: quotes and backslashes are backslash-escaped and control characters
+/// are written as `\n`, `\t`, `\u0000` and so on. The surrounding quotes are not added.
+///
+/// ### Parameters
+/// - `value`: A string slice (`&str`) to escape.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::escape_json;
+///
+/// let escaped = escape_json("He said \"hi\"\n");
+/// assert_eq!(escaped, r#"He said \"hi\"\n"#);
+/// ```
+/// <small>End Fun Doc</small>
+pub fn escape_json(value: &str) -> String {
+    Escape::Json.apply(value).into_owned()
+}
+
+/// ### Raw(value)
+///
+/// Trusted Content Wrapper
+///
+/// The `Raw` wrapper marks a value as trusted markup that must be inserted without escaping,
+/// even by `do_html!`, `do_xml!` and `do_json!`. Use it for partials such as a shared `HEAD`
+/// or for script tags produced by `alpine` and `chart_js`. Never wrap user input in `Raw`.
+///
+/// ### Examples
+/// ```rust
+/// use cans::do_html;
+/// use cans::content::Raw;
+///
+/// let template = "<div>{{menu}}{{name}}</div>";
+/// let result = do_html!(template, menu = Raw("<nav></nav>"), name = "<Ahmed>");
+/// assert_eq!(result, "<div><nav></nav>&lt;Ahmed&gt;</div>");
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Raw<T>(pub T);
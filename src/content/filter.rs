@@ -0,0 +1,200 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+use super::escape::{escape_html, escape_json, escape_xml};
+use super::value::Value;
+
+/// The signature of a custom filter: it receives the value and the filter's arguments and
+/// returns the new value, or a message explaining why it cannot be applied.
+type FilterFn = dyn for<'a> Fn(Value<'a>, &[&str]) -> Result<Value<'a>, String> + Send + Sync;
+
+/// A custom filter registered on a `TemplateRegistry`.
+#[derive(Clone)]
+pub(crate) struct Filter(Arc<FilterFn>);
+
+impl Filter {
+    pub(crate) fn new<F>(filter: F) -> Self
+    where
+        F: for<'a> Fn(Value<'a>, &[&str]) -> Result<Value<'a>, String> + Send + Sync + 'static,
+    {
+        Filter(Arc::new(filter))
+    }
+
+    pub(crate) fn apply<'a>(&self, value: Value<'a>, args: &[&str]) -> Result<Value<'a>, String> {
+        (self.0)(value, args)
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Filter(..)")
+    }
+}
+
+/// Applies the built-in filter `name`, or returns `None` if there is no such filter.
+pub(crate) fn builtin<'a>(name: &str, value: Value<'a>, args: &[&str]) -> Option<Result<Value<'a>, String>> {
+    let result = match name {
+        "upper" => Ok(map_text(value, |text| text.to_uppercase())),
+        "lower" => Ok(map_text(value, |text| text.to_lowercase())),
+        "capitalize" => Ok(map_text(value, |text| capitalize(text, false))),
+        "title" => Ok(map_text(value, |text| capitalize(text, true))),
+        "trim" => Ok(map_text(value, |text| text.trim().to_string())),
+        "truncate" => truncate(value, args),
+        "default" => match args.first() {
+            Some(_) if value.is_truthy() => Ok(value),
+            Some(default) => Ok(Value::Raw(Cow::Owned(default.to_string()))),
+            None => Err("expects the default text, as in `default:\"none\"`".to_string()),
+        },
+        "join" => Ok(join(value, args.first().copied().unwrap_or(", "))),
+        "round" => round(value, args),
+        "number" => number(value, args),
+        "raw" => Ok(match value {
+            Value::Text(text) | Value::Json(text) => Value::Raw(text),
+            value => value,
+        }),
+        "escape_html" => Ok(Value::Raw(Cow::Owned(escape_html(&value.to_string())))),
+        "escape_xml" => Ok(Value::Raw(Cow::Owned(escape_xml(&value.to_string())))),
+        "escape_json" => Ok(Value::Raw(Cow::Owned(escape_json(&value.to_string())))),
+        "urlencode" => Ok(map_text(value, url_encode)),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Transforms the text of `value`, or of each item of a list, keeping `Raw` text raw. Other
+/// values are converted to text.
+fn map_text<'a>(value: Value<'a>, f: impl Fn(&str) -> String + Copy) -> Value<'a> {
+    match value {
+        Value::Raw(text) => Value::Raw(Cow::Owned(f(&text))),
+        Value::Text(text) => Value::Text(Cow::Owned(f(&text))),
+        Value::List(items) => Value::List(items.into_iter().map(|item| map_text(item, f)).collect()),
+        value => Value::Text(Cow::Owned(f(&value.to_string()))),
+    }
+}
+
+/// Upper-cases the first letter of `text`, or of every word when `words` is set.
+fn capitalize(text: &str, words: bool) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut at_start = true;
+    for c in text.chars() {
+        if at_start && c.is_alphanumeric() {
+            output.extend(c.to_uppercase());
+            at_start = false;
+        } else {
+            output.push(c);
+            if words && !c.is_alphanumeric() {
+                at_start = true;
+            }
+        }
+    }
+    output
+}
+
+/// `truncate:length` or `truncate:length,"suffix"`: keeps the first `length` characters and
+/// appends the suffix (`...` by default) when anything was cut.
+fn truncate<'a>(value: Value<'a>, args: &[&str]) -> Result<Value<'a>, String> {
+    let length: usize = match args.first() {
+        Some(length) => length.parse().map_err(|_| format!("`{length}` is not a length"))?,
+        None => return Err("expects a length, as in `truncate:120`".to_string()),
+    };
+    let suffix = args.get(1).copied().unwrap_or("...");
+    Ok(map_text(value, |text| match text.char_indices().nth(length) {
+        Some((cut, _)) => format!("{}{suffix}", &text[..cut]),
+        None => text.to_string(),
+    }))
+}
+
+/// Joins the items of a list into text separated by `separator`. The result is escaped like
+/// any other text, separator included.
+pub(crate) fn join<'a>(value: Value<'a>, separator: &str) -> Value<'a> {
+    let Value::List(items) = value else {
+        return value;
+    };
+    let items: Vec<String> = items.iter().map(Value::to_string).collect();
+    Value::Text(Cow::Owned(items.join(separator)))
+}
+
+/// `round` or `round:places`: formats a number, or text holding a number, with a fixed number
+/// of decimal places (none by default).
+fn round<'a>(value: Value<'a>, args: &[&str]) -> Result<Value<'a>, String> {
+    let places: usize = match args.first() {
+        Some(places) => places.parse().map_err(|_| format!("`{places}` is not a number of decimal places"))?,
+        None => 0,
+    };
+    // Integers are written exactly, without going through `f64`.
+    format_number(&value, Some(places), "", ".").map(|number| Value::Text(Cow::Owned(number)))
+}
+
+/// `number`, `number:places`, `number:places,"thousands"` or
+/// `number:places,"thousands","decimal point"`: formats a number with a fixed number of decimal
+/// places (as many as needed when `places` is empty) and digit grouping.
+fn number<'a>(value: Value<'a>, args: &[&str]) -> Result<Value<'a>, String> {
+    let places = match args.first() {
+        Some(&"") | None => None,
+        Some(places) => Some(places.parse().map_err(|_| format!("`{places}` is not a number of decimal places"))?),
+    };
+    let thousands = args.get(1).copied().unwrap_or("");
+    let decimal_point = args.get(2).copied().unwrap_or(".");
+    format_number(&value, places, thousands, decimal_point).map(|text| Value::Text(Cow::Owned(text)))
+}
+
+/// Formats an integer, a float or text holding a number with `places` decimal places, or as
+/// many as needed when `places` is `None`, grouping the digits before the decimal point in
+/// threes with `thousands` and writing `decimal_point` before the decimals.
+pub(crate) fn format_number(value: &Value<'_>, places: Option<usize>, thousands: &str, decimal_point: &str) -> Result<String, String> {
+    let digits = match (value, places) {
+        (Value::Int(number), None | Some(0)) => number.to_string(),
+        (Value::Int(number), Some(places)) => format!("{number}.{}", "0".repeat(places)),
+        (Value::Float(number), None) => number.to_string(),
+        (Value::Float(number), Some(places)) => format!("{number:.places$}"),
+        (Value::Text(text) | Value::Raw(text), _) => {
+            let text = text.trim();
+            let number = match text.parse::<i128>() {
+                Ok(number) => Value::Int(number),
+                Err(_) => Value::Float(text.parse().map_err(|_| format!("`{text}` is not a number"))?),
+            };
+            return format_number(&number, places, thousands, decimal_point);
+        }
+        (value, _) => return Err(format!("{value:?} is not a number")),
+    };
+    if !digits.bytes().all(|byte| byte.is_ascii_digit() || byte == b'-' || byte == b'.') {
+        // `NaN` and `inf` have no digits to group.
+        return Ok(digits);
+    }
+
+    let (sign, digits) = match digits.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", digits.as_str()),
+    };
+    let (whole, decimals) = match digits.split_once('.') {
+        Some((whole, decimals)) => (whole, Some(decimals)),
+        None => (digits, None),
+    };
+    let mut output = String::with_capacity(digits.len() + whole.len() / 3 * thousands.len() + 1);
+    output.push_str(sign);
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            output.push_str(thousands);
+        }
+        output.push(digit);
+    }
+    if let Some(decimals) = decimals {
+        output.push_str(decimal_point);
+        output.push_str(decimals);
+    }
+    Ok(output)
+}
+
+/// Percent-encodes every byte except ASCII letters, digits, `-`, `_`, `.` and `~`.
+fn url_encode(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            output.push(byte as char);
+        } else {
+            output.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    output
+}
@@ -0,0 +1,182 @@
+use std::fmt::{self, Display};
+use std::io;
+
+use super::sink;
+
+/// Writes one item of a loop from its index and the item.
+type ItemFn<'a, T> = Box<dyn Fn(usize, &T) -> String + 'a>;
+
+/// ### ForLoop
+///
+/// Loop Builder
+///
+/// A `ForLoop` formats a slice of items like `do_forloop`, with the options that a plain
+/// prefix and suffix cannot express: a `separator` written between items but not after the
+/// last one, an `empty` fallback written instead of everything else when the slice is empty,
+/// and a closure that receives the index of each item, so that the first or last item can be
+/// written differently. Every option is optional, and the text returned by the closure is
+/// inserted as it is, without escaping.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::ForLoop;
+///
+/// let tags = ["rust", "web", "templates"];
+/// assert_eq!(ForLoop::new(&tags).separator(", ").render(), "rust, web, templates");
+///
+/// let list = ForLoop::new(&tags)
+///     .before_items("<ul>")
+///     .after_items("</ul>")
+///     .map(|index, tag| match index {
+///         0 => format!("<li class=\"first\">{tag}</li>"),
+///         index if index + 1 == tags.len() => format!("<li class=\"last\">{tag}</li>"),
+///         _ => format!("<li>{tag}</li>"),
+///     })
+///     .render();
+/// assert_eq!(list, "<ul><li class=\"first\">rust</li><li>web</li><li class=\"last\">templates</li></ul>");
+///
+/// let none: [&str; 0] = [];
+/// assert_eq!(ForLoop::new(&none).before_items("<ul>").after_items("</ul>").empty("<p>No tags</p>").render(), "<p>No tags</p>");
+/// ```
+///
+/// #### Example: Items that do not implement Display
+/// ```rust
+/// use cans::content::ForLoop;
+///
+/// struct Post {
+///     title: &'static str,
+/// }
+///
+/// let posts = [Post { title: "Hello" }, Post { title: "World" }];
+/// let numbered = ForLoop::from_fn(&posts, |index, post| format!("{}. {}", index + 1, post.title))
+///     .separator("\n")
+///     .render();
+/// assert_eq!(numbered, "1. Hello\n2. World");
+/// ```
+/// <small>End Doc</small>
+pub struct ForLoop<'a, T> {
+    items: &'a [T],
+    before_items: &'a str,
+    before_item: &'a str,
+    after_item: &'a str,
+    after_items: &'a str,
+    separator: &'a str,
+    empty: Option<&'a str>,
+    item: ItemFn<'a, T>,
+}
+
+impl<'a, T: Display> ForLoop<'a, T> {
+    /// Creates a loop that writes each item with its `Display` implementation.
+    pub fn new(items: &'a [T]) -> Self {
+        Self::from_fn(items, |_, item| item.to_string())
+    }
+}
+
+impl<'a, T> ForLoop<'a, T> {
+    /// Creates a loop that writes each item with the text returned by `item`, which receives the
+    /// index of the item and the item itself.
+    pub fn from_fn<F>(items: &'a [T], item: F) -> Self
+    where
+        F: Fn(usize, &T) -> String + 'a,
+    {
+        ForLoop {
+            items,
+            before_items: "",
+            before_item: "",
+            after_item: "",
+            after_items: "",
+            separator: "",
+            empty: None,
+            item: Box::new(item),
+        }
+    }
+
+    /// Sets the closure that writes each item, replacing the one set before.
+    pub fn map<F>(mut self, item: F) -> Self
+    where
+        F: Fn(usize, &T) -> String + 'a,
+    {
+        self.item = Box::new(item);
+        self
+    }
+
+    /// Sets the text written before the whole collection of items.
+    pub fn before_items(mut self, text: &'a str) -> Self {
+        self.before_items = text;
+        self
+    }
+
+    /// Sets the text written before each item.
+    pub fn before_item(mut self, text: &'a str) -> Self {
+        self.before_item = text;
+        self
+    }
+
+    /// Sets the text written after each item.
+    pub fn after_item(mut self, text: &'a str) -> Self {
+        self.after_item = text;
+        self
+    }
+
+    /// Sets the text written after the whole collection of items.
+    pub fn after_items(mut self, text: &'a str) -> Self {
+        self.after_items = text;
+        self
+    }
+
+    /// Sets the text written between two items, after the `after_item` text of the first one.
+    pub fn separator(mut self, text: &'a str) -> Self {
+        self.separator = text;
+        self
+    }
+
+    /// Sets the text written instead of the items, and of the text around them, when there are
+    /// no items.
+    pub fn empty(mut self, text: &'a str) -> Self {
+        self.empty = Some(text);
+        self
+    }
+
+    /// Formats the items into a new `String`.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        self.render_to(&mut output).expect("writing to a String does not fail");
+        output
+    }
+
+    /// Writes the items into a `std::fmt::Write` sink.
+    pub fn render_to<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        if let (true, Some(empty)) = (self.items.is_empty(), self.empty) {
+            return out.write_str(empty);
+        }
+        out.write_str(self.before_items)?;
+        for (index, item) in self.items.iter().enumerate() {
+            if index > 0 {
+                out.write_str(self.separator)?;
+            }
+            out.write_str(self.before_item)?;
+            out.write_str(&(self.item)(index, item))?;
+            out.write_str(self.after_item)?;
+        }
+        out.write_str(self.after_items)
+    }
+
+    /// Writes the items straight into a `std::io::Write` sink.
+    pub fn write_to<W: io::Write + ?Sized>(&self, out: &mut W) -> io::Result<()> {
+        sink::write_io(out, |out| self.render_to(out))
+    }
+}
+
+impl<T> fmt::Debug for ForLoop<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForLoop")
+            .field("items", &self.items.len())
+            .field("before_items", &self.before_items)
+            .field("before_item", &self.before_item)
+            .field("after_item", &self.after_item)
+            .field("after_items", &self.after_items)
+            .field("separator", &self.separator)
+            .field("empty", &self.empty)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,395 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+use super::escape::Escape;
+use super::value::{TemplateValue, Value};
+
+/// ### ToJson
+///
+/// JSON Conversion Trait
+///
+/// The `ToJson` trait writes a Rust value as JSON without serde. Strings and characters are
+/// written as quoted, escaped JSON strings, booleans and numbers are written unquoted, `None`
+/// and non-finite floats are written as `null`, sequences (`Vec`, slices and arrays) as arrays,
+/// and maps with string keys as objects. `JsonArray` and `JsonObject` are written as the JSON
+/// they hold, so they can be nested in each other and in any of the above. It can be
+/// implemented for your own types, usually by writing a `JsonObject`.
+///
+/// ### Examples
+/// ```rust
+/// use std::collections::BTreeMap;
+/// use cans::content::{JsonObject, ToJson};
+///
+/// assert_eq!("say \"hi\"".to_json(), r#""say \"hi\"""#);
+/// assert_eq!(vec![Some(1.5), None, Some(f64::NAN)].to_json(), "[1.5,null,null]");
+///
+/// let mut scores = BTreeMap::new();
+/// scores.insert("amal", vec![90, 85]);
+/// assert_eq!(scores.to_json(), r#"{"amal":[90,85]}"#);
+///
+/// struct User {
+///     name: String,
+///     admin: bool,
+/// }
+///
+/// impl ToJson for User {
+///     fn write_json<W: std::fmt::Write + ?Sized>(&self, out: &mut W) -> std::fmt::Result {
+///         JsonObject::new().field("name", &self.name).field("admin", self.admin).write_json(out)
+///     }
+/// }
+///
+/// let user = User { name: "Ahmed".to_string(), admin: true };
+/// assert_eq!(user.to_json(), r#"{"name":"Ahmed","admin":true}"#);
+/// ```
+/// <small>End Doc</small>
+pub trait ToJson {
+    /// Writes the value as JSON into `out`.
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result;
+
+    /// Returns the value as a JSON `String`.
+    fn to_json(&self) -> String {
+        let mut output = String::new();
+        self.write_json(&mut output).expect("writing to a String does not fail");
+        output
+    }
+}
+
+/// Writes `text` as a quoted JSON string.
+fn write_string<W: fmt::Write + ?Sized>(out: &mut W, text: &str) -> fmt::Result {
+    out.write_char('"')?;
+    out.write_str(&Escape::Json.apply(text))?;
+    out.write_char('"')
+}
+
+/// Writes the items of a sequence as a JSON array.
+fn write_array<'i, W, T, I>(out: &mut W, items: I) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+    T: ToJson + 'i,
+    I: IntoIterator<Item = &'i T>,
+{
+    out.write_char('[')?;
+    for (index, item) in items.into_iter().enumerate() {
+        if index > 0 {
+            out.write_char(',')?;
+        }
+        item.write_json(out)?;
+    }
+    out.write_char(']')
+}
+
+/// Writes the entries of a map as a JSON object.
+fn write_object<'i, W, K, V, I>(out: &mut W, entries: I) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+    K: Borrow<str> + 'i,
+    V: ToJson + 'i,
+    I: IntoIterator<Item = (&'i K, &'i V)>,
+{
+    out.write_char('{')?;
+    for (index, (key, value)) in entries.into_iter().enumerate() {
+        if index > 0 {
+            out.write_char(',')?;
+        }
+        write_string(out, key.borrow())?;
+        out.write_char(':')?;
+        value.write_json(out)?;
+    }
+    out.write_char('}')
+}
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        (**self).write_json(out)
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for Box<T> {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        (**self).write_json(out)
+    }
+}
+
+impl ToJson for str {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_string(out, self)
+    }
+}
+
+impl ToJson for String {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_string(out, self)
+    }
+}
+
+impl ToJson for Cow<'_, str> {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_string(out, self)
+    }
+}
+
+impl ToJson for char {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_string(out, self.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl ToJson for bool {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write!(out, "{self}")
+    }
+}
+
+impl ToJson for f32 {
+    /// Non-finite values are written as `null`, which is the closest JSON has to them.
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        if self.is_finite() { write!(out, "{self}") } else { out.write_str("null") }
+    }
+}
+
+impl ToJson for f64 {
+    /// Non-finite values are written as `null`, which is the closest JSON has to them.
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        if self.is_finite() { write!(out, "{self}") } else { out.write_str("null") }
+    }
+}
+
+macro_rules! integer_json {
+    ($($ty:ty),*) => {
+        $(
+            impl ToJson for $ty {
+                fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+                    write!(out, "{self}")
+                }
+            }
+        )*
+    };
+}
+
+integer_json!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        match self {
+            Some(value) => value.write_json(out),
+            None => out.write_str("null"),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_array(out, self)
+    }
+}
+
+impl<T: ToJson, const N: usize> ToJson for [T; N] {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_array(out, self)
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_array(out, self)
+    }
+}
+
+impl<K, V, S> ToJson for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    V: ToJson,
+    S: BuildHasher,
+{
+    /// The entries are written in the map's iteration order.
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_object(out, self)
+    }
+}
+
+impl<K, V> ToJson for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: ToJson,
+{
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        write_object(out, self)
+    }
+}
+
+/// ### JsonArray
+///
+/// JSON Array Builder
+///
+/// A `JsonArray` builds a JSON array one item at a time from any `ToJson` value, including other
+/// arrays and `JsonObject`s, so items of different types can be mixed. It can also be collected
+/// from an iterator. Its `Display` implementation writes the JSON. Inserted into a `do_json!`
+/// template, it is written as JSON, while `do_html!` and `do_xml!` escape it like any other
+/// text, so that its strings are never written as markup.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{JsonArray, JsonObject};
+/// use cans::do_json;
+///
+/// let array = JsonArray::new().push("a \"quoted\" word").push(42).push(false).push(JsonArray::new());
+/// assert_eq!(array.to_string(), r#"["a \"quoted\" word",42,false,[]]"#);
+///
+/// let posts = [("Hello", 3), ("World", 0)];
+/// let items: JsonArray = posts.iter().map(|(title, likes)| JsonObject::new().field("title", *title).field("likes", *likes)).collect();
+/// assert_eq!(
+///     do_json!(r#"{"posts":{{posts}}}"#, posts = items),
+///     r#"{"posts":[{"title":"Hello","likes":3},{"title":"World","likes":0}]}"#
+/// );
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonArray {
+    json: String,
+}
+
+impl Default for JsonArray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonArray {
+    /// Creates an empty array.
+    pub fn new() -> Self {
+        JsonArray { json: String::from("[") }
+    }
+
+    /// Appends `value` to the array.
+    pub fn push(mut self, value: impl ToJson) -> Self {
+        if self.json.len() > 1 {
+            self.json.push(',');
+        }
+        value.write_json(&mut self.json).expect("writing to a String does not fail");
+        self
+    }
+
+    /// Returns `true` if no item has been pushed.
+    pub fn is_empty(&self) -> bool {
+        self.json.len() == 1
+    }
+
+    /// Returns the JSON text of the array.
+    pub fn into_string(mut self) -> String {
+        self.json.push(']');
+        self.json
+    }
+}
+
+impl<T: ToJson> FromIterator<T> for JsonArray {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        items.into_iter().fold(JsonArray::new(), JsonArray::push)
+    }
+}
+
+impl ToJson for JsonArray {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        out.write_str(&self.json)?;
+        out.write_char(']')
+    }
+}
+
+impl fmt::Display for JsonArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_json(f)
+    }
+}
+
+impl TemplateValue for JsonArray {
+    /// Inserted as JSON into JSON templates, and escaped like text for other formats.
+    fn to_value(&self) -> Value<'_> {
+        Value::Json(Cow::Owned(self.to_json()))
+    }
+}
+
+/// ### JsonObject
+///
+/// JSON Object Builder
+///
+/// A `JsonObject` builds a JSON object one field at a time from any `ToJson` value, including
+/// other objects and `JsonArray`s. Keys are quoted and escaped like strings, and fields are
+/// written in the order they were added. Its `Display` implementation writes the JSON. Like a
+/// `JsonArray`, it is written as JSON into a `do_json!` template and escaped for other formats.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::JsonObject;
+///
+/// let author = JsonObject::new().field("name", "Amal \"the\" Writer").field("email", None::<&str>);
+/// let post = JsonObject::new()
+///     .field("id", 7)
+///     .field("tags", ["rust", "json"])
+///     .field("rating", 4.5)
+///     .field("author", author);
+/// assert_eq!(
+///     post.to_string(),
+///     r#"{"id":7,"tags":["rust","json"],"rating":4.5,"author":{"name":"Amal \"the\" Writer","email":null}}"#
+/// );
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonObject {
+    json: String,
+}
+
+impl Default for JsonObject {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonObject {
+    /// Creates an empty object.
+    pub fn new() -> Self {
+        JsonObject { json: String::from("{") }
+    }
+
+    /// Adds the field `key` with `value` to the object.
+    pub fn field(mut self, key: &str, value: impl ToJson) -> Self {
+        if self.json.len() > 1 {
+            self.json.push(',');
+        }
+        write_string(&mut self.json, key).expect("writing to a String does not fail");
+        self.json.push(':');
+        value.write_json(&mut self.json).expect("writing to a String does not fail");
+        self
+    }
+
+    /// Returns `true` if no field has been added.
+    pub fn is_empty(&self) -> bool {
+        self.json.len() == 1
+    }
+
+    /// Returns the JSON text of the object.
+    pub fn into_string(mut self) -> String {
+        self.json.push('}');
+        self.json
+    }
+}
+
+impl ToJson for JsonObject {
+    fn write_json<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        out.write_str(&self.json)?;
+        out.write_char('}')
+    }
+}
+
+impl fmt::Display for JsonObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_json(f)
+    }
+}
+
+impl TemplateValue for JsonObject {
+    /// Inserted as JSON into JSON templates, and escaped like text for other formats.
+    fn to_value(&self) -> Value<'_> {
+        Value::Json(Cow::Owned(self.to_json()))
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+use std::time::SystemTime;
+
+use super::context::Context;
+use super::delimiters::Delimiters;
+use super::error::RenderError;
+use super::escape::Escape;
+use super::registry::TemplateRegistry;
+use super::value::Value;
+
+/// ### TemplateLoader
+///
+/// Template Directory Loader
+///
+/// A `TemplateLoader` reads every file below a directory into a `TemplateRegistry`, named by its
+/// path relative to the directory with `/` separators, such as `home.html` or
+/// `partials/card.html`, so that templates can include and extend each other by those names.
+/// Hidden files are skipped. Values are escaped as HTML by default, like `do_html!`.
+///
+/// A loader created with `new` checks the modification times of the files before each render
+/// in debug builds and reloads the templates that changed, were added or were removed, so that
+/// templates can be edited without rebuilding; if the directory cannot be read, the templates
+/// loaded before are kept. `from_embedded` serves templates embedded with `embed_templates!`.
+/// The `template_loader!` macro picks between the two: it reads the directory in debug builds
+/// and embeds it into the binary in release builds.
+///
+/// A loader can be shared between threads.
+///
+/// ### Examples
+/// ```rust
+/// use cans::template_loader;
+///
+/// #[derive(cans::Context)]
+/// struct Post {
+///     title: &'static str,
+/// }
+///
+/// #[derive(cans::Context)]
+/// struct Home {
+///     title: &'static str,
+///     posts: Vec<Post>,
+/// }
+///
+/// // tests/templates/home.html:
+/// // <h1>{{title}}</h1>{{#each posts as post}}{{> partials/card.html title=post.title}}{{/each}}
+/// let loader = template_loader!("tests/templates").unwrap();
+/// let home = Home { title: "News", posts: vec![Post { title: "Fish & Chips" }] };
+/// assert_eq!(
+///     loader.render("home.html", &home).unwrap(),
+///     "<h1>News</h1><article>Fish &amp; Chips</article>"
+/// );
+/// ```
+///
+/// #### Example: Embedded templates
+/// ```rust
+/// use cans::content::{Escape, TemplateLoader};
+///
+/// let loader = TemplateLoader::from_embedded(cans::embed_templates!("tests/templates")).with_escape(Escape::None);
+/// assert!(loader.contains("partials/card.html"));
+/// assert_eq!(loader.render("partials/card.html", &[("title", "<b>")]).unwrap(), "<article><b></article>");
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug)]
+pub struct TemplateLoader {
+    /// The directory templates are read from, or `None` for embedded templates.
+    dir: Option<PathBuf>,
+    hot_reload: bool,
+    state: RwLock<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    registry: TemplateRegistry,
+    /// The modification time of every template read from the directory, by name.
+    modified: HashMap<String, SystemTime>,
+}
+
+impl TemplateLoader {
+    /// Reads every template below `dir`. Changed files are reloaded before each render in debug
+    /// builds; see `with_hot_reload`.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let loader = TemplateLoader {
+            dir: Some(dir.into()),
+            hot_reload: cfg!(debug_assertions),
+            state: RwLock::new(State { registry: TemplateRegistry::new().with_escape(Escape::Html), modified: HashMap::new() }),
+        };
+        loader.reload()?;
+        Ok(loader)
+    }
+
+    /// Serves the `(name, source)` pairs of `files`, usually produced by `embed_templates!`.
+    pub fn from_embedded(files: &[(&str, &str)]) -> Self {
+        let mut registry = TemplateRegistry::new().with_escape(Escape::Html);
+        for (name, source) in files {
+            registry.register(*name, *source);
+        }
+        TemplateLoader {
+            dir: None,
+            hot_reload: false,
+            state: RwLock::new(State { registry, modified: HashMap::new() }),
+        }
+    }
+
+    /// Sets whether changed files are reloaded before each render. It is on by default in debug
+    /// builds and has no effect on embedded templates.
+    pub fn with_hot_reload(mut self, hot_reload: bool) -> Self {
+        self.hot_reload = hot_reload;
+        self
+    }
+
+    /// Sets how values are escaped and parses the templates again.
+    pub fn with_escape(self, escape: Escape) -> Self {
+        self.configure(|registry| registry.with_escape(escape))
+    }
+
+    /// Sets the tag delimiters and parses the templates again. See `Delimiters`.
+    pub fn with_delimiters(self, delimiters: Delimiters) -> Self {
+        self.configure(|registry| registry.with_delimiters(delimiters))
+    }
+
+    /// Sets whether multi-line values are indented to the column of their placeholder and
+    /// parses the templates again. See `Template::with_indent`.
+    pub fn with_indent(self, indent: bool) -> Self {
+        self.configure(|registry| registry.with_indent(indent))
+    }
+
+    /// Sets how deeply partials may be nested.
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        self.configure(|registry| registry.with_max_depth(max_depth))
+    }
+
+    /// Registers a custom filter. See `TemplateRegistry::register_filter`.
+    pub fn register_filter<F>(&mut self, name: impl Into<String>, filter: F) -> &mut Self
+    where
+        F: for<'a> Fn(Value<'a>, &[&str]) -> Result<Value<'a>, String> + Send + Sync + 'static,
+    {
+        let state = self.state.get_mut().unwrap_or_else(PoisonError::into_inner);
+        state.registry.register_filter(name, filter);
+        self
+    }
+
+    /// Returns `true` if a template is loaded under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.refresh().registry.contains(name)
+    }
+
+    /// Renders the template loaded under `name`, or returns `None` if there is no such template.
+    pub fn render<C: Context + ?Sized>(&self, name: &str, context: &C) -> Option<String> {
+        self.refresh().registry.render(name, context)
+    }
+
+    /// Renders the template loaded under `name` like `render`, but returns a `RenderError`
+    /// listing every problem found in it or in its partials.
+    pub fn render_strict<C: Context + ?Sized>(&self, name: &str, context: &C) -> Result<String, RenderError> {
+        self.refresh().registry.render_strict(name, context)
+    }
+
+    /// Renders the template loaded under `name` into `out`, or returns `None` if there is no
+    /// such template.
+    pub fn render_to<W, C>(&self, name: &str, out: &mut W, context: &C) -> Option<fmt::Result>
+    where
+        W: fmt::Write + ?Sized,
+        C: Context + ?Sized,
+    {
+        self.refresh().registry.render_to(name, out, context)
+    }
+
+    /// Renders the template loaded under `name` straight into an `io::Write` sink, or returns
+    /// `None` if there is no such template.
+    pub fn write_to<W, C>(&self, name: &str, out: &mut W, context: &C) -> Option<io::Result<()>>
+    where
+        W: io::Write + ?Sized,
+        C: Context + ?Sized,
+    {
+        self.refresh().registry.write_to(name, out, context)
+    }
+
+    /// Reads the templates that were added or changed since they were last read, and drops the
+    /// ones whose files were removed. Returns `true` if anything changed. Embedded templates are
+    /// never reloaded.
+    pub fn reload(&self) -> io::Result<bool> {
+        let Some(dir) = &self.dir else {
+            return Ok(false);
+        };
+        let mut files = Vec::new();
+        list_files(dir, "", &mut files)?;
+
+        let (changed, removed) = {
+            let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+            let removed: Vec<String> = state
+                .modified
+                .keys()
+                .filter(|name| !files.iter().any(|(file, ..)| file == *name))
+                .cloned()
+                .collect();
+            let changed: Vec<_> = files
+                .into_iter()
+                .filter(|(name, _, modified)| state.modified.get(name) != Some(modified))
+                .collect();
+            (changed, removed)
+        };
+        if changed.is_empty() && removed.is_empty() {
+            return Ok(false);
+        }
+
+        let mut sources = Vec::with_capacity(changed.len());
+        for (name, path, modified) in changed {
+            sources.push((name, fs::read_to_string(path)?, modified));
+        }
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        for name in removed {
+            state.registry.remove(&name);
+            state.modified.remove(&name);
+        }
+        for (name, source, modified) in sources {
+            state.registry.register(name.clone(), source);
+            state.modified.insert(name, modified);
+        }
+        Ok(true)
+    }
+
+    /// Reloads changed templates when hot reloading is on and locks the state for reading.
+    fn refresh(&self) -> RwLockReadGuard<'_, State> {
+        if self.hot_reload {
+            // Keep serving the templates loaded before when the directory cannot be read.
+            let _ = self.reload();
+        }
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Applies new settings to the registry and parses every template again with them.
+    fn configure(mut self, configure: impl FnOnce(TemplateRegistry) -> TemplateRegistry) -> Self {
+        let state = self.state.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let mut registry = configure(std::mem::take(&mut state.registry));
+        let sources: Vec<(String, String)> = registry
+            .names()
+            .filter_map(|name| Some((name.to_string(), registry.get(name)?.source().to_string())))
+            .collect();
+        for (name, source) in sources {
+            registry.register(name, source);
+        }
+        state.registry = registry;
+        self
+    }
+}
+
+/// Lists the files below `dir` with their names relative to the template directory and their
+/// modification times, skipping hidden files and directories.
+fn list_files(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf, SystemTime)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let name = format!("{prefix}{file_name}");
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            list_files(&entry.path(), &format!("{name}/"), files)?;
+        } else {
+            files.push((name, entry.path(), metadata.modified()?));
+        }
+    }
+    Ok(())
+}
@@ -2,6 +2,117 @@ pub use crate::do_replace;
 pub use crate::do_html;
 pub use crate::do_xml;
 pub use crate::do_json;
+pub use crate::try_do_replace;
+pub use crate::try_do_html;
+pub use crate::try_do_xml;
+pub use crate::try_do_json;
+pub use crate::do_html_file;
+pub use crate::template_loader;
+
+mod context;
+mod delimiters;
+mod error;
+mod escape;
+mod filter;
+mod forloop;
+mod json;
+mod loader;
+pub(crate) mod parser;
+mod placeholder;
+mod registry;
+mod render;
+mod sink;
+mod template;
+mod value;
+
+pub use cans_macros::{embed_templates, Context};
+pub use context::{from_fn, Context, FromFn};
+pub use delimiters::Delimiters;
+pub use error::{Issue, IssueKind, RenderError};
+pub use escape::{escape_html, escape_json, escape_xml, Escape, Raw};
+pub use forloop::ForLoop;
+pub use json::{JsonArray, JsonObject, ToJson};
+pub use loader::TemplateLoader;
+pub use placeholder::{placeholders, Placeholder, PlaceholderKind};
+pub use registry::TemplateRegistry;
+pub use template::Template;
+pub use value::{Join, Lazy, Number, TemplateValue, Value};
+
+/// ### do_substitute(content, values)
+///
+/// Placeholder Substitution Function
+///
+/// The `do_substitute` function scans `content` once, fills every `{{key}}` placeholder with
+/// the value paired with `key`, and writes the result into a single output buffer. Values are
+/// inserted as they are and are never scanned again, so a value containing `{{other}}` stays
+/// literal text and the result does not depend on the order of the pairs. Placeholders without
+/// a value are left unchanged. When a key appears more than once, the first pair wins.
+/// It is a shorthand for rendering a `Template` with `Escape::None`.
+///
+/// ### Parameters
+/// - `content`: The content containing placeholders (e.g., `"<p>Hello, {{name}}!</p>"`).
+/// - `values`: A slice of `(key, value)` pairs; the values are inserted without escaping.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::do_substitute;
+///
+/// let result = do_substitute("<p>{{greeting}}, {{name}}!</p>", &[("name", "Ahmed"), ("greeting", "Hi")]);
+/// assert_eq!(result, "<p>Hi, Ahmed!</p>");
+///
+/// // Values are never expanded again.
+/// let result = do_substitute("{{a}} {{b}}", &[("a", "{{b}}"), ("b", "B")]);
+/// assert_eq!(result, "{{b}} B");
+/// ```
+/// <small>End Fun Doc</small>
+pub fn do_substitute<C, V>(content: C, values: &[(&str, V)]) -> String
+where
+    C: AsRef<str>,
+    V: TemplateValue,
+{
+    Template::new(content.as_ref()).render(values)
+}
+
+/// ### render_with(template, context)
+///
+/// Runtime Context Rendering Function
+///
+/// The `render_with` function fills the `{{key}}` placeholders of `template` with values looked
+/// up in `context` at run time. It works like `do_replace!`, but the keys do not have to be Rust
+/// identifiers known at compile time: any `Context`, such as a `HashMap<String, String>` loaded
+/// from a CMS or a configuration file, a `BTreeMap`, a slice of pairs or a closure, can be used.
+/// Values are inserted without escaping; use `Template::with_escape` to escape them.
+///
+/// ### Parameters
+/// - `template`: The template containing placeholders (e.g., `"<p>Hello, {{name}}!</p>"`).
+/// - `context`: The `Context` that supplies the placeholder values.
+///
+/// ### Examples
+/// ```rust
+/// use std::collections::{BTreeMap, HashMap};
+/// use cans::content::render_with;
+///
+/// let mut fields = HashMap::new();
+/// fields.insert("site-title".to_string(), "CANS".to_string());
+/// fields.insert("year".to_string(), "2025".to_string());
+/// assert_eq!(render_with("{{site-title}} © {{year}}", &fields), "CANS © 2025");
+///
+/// let mut config = BTreeMap::new();
+/// config.insert("host", "localhost");
+/// config.insert("port", "8080");
+/// assert_eq!(render_with("http://{{host}}:{{port}}", &config), "http://localhost:8080");
+///
+/// let pairs = [("name", "Ahmed")];
+/// assert_eq!(render_with("Hi {{name}}", &pairs[..]), "Hi Ahmed");
+/// ```
+/// <small>End Fun Doc</small>
+pub fn render_with<T, C>(template: T, context: &C) -> String
+where
+    T: AsRef<str>,
+    C: Context + ?Sized,
+{
+    Template::new(template.as_ref()).render(context)
+}
 
 /// ### do_forloop(vector, befor_items, befor_item, after_item, after_items)
 ///
@@ -10,7 +121,9 @@ pub use crate::do_json;
 /// The `do_forloop` function takes a vector of items and formats them according to specified prefixes
 /// and suffixes for the entire collection, as well as for each individual item. The output
 /// is a concatenated string representation of the items, encapsulated within the provided
-/// separator strings.
+/// separator strings. To write separators between items, an `empty` fallback or items that
+/// depend on their index, use `ForLoop`. To render a sub-template for each item, use an
+/// `{{#each items as item}}...{{/each}}` block in the template instead.
 ///
 /// ### Parameters
 /// - `vector`: A slice of items to format, where each item must implement the `Display` trait.
@@ -39,12 +152,120 @@ pub fn do_forloop<T: std::fmt::Display>(
     after_item: &str,
     after_items: &str,
 ) -> String {
-    let items = vector
-        .iter()
-        .map(|item| format!("{befor_item}{item}{after_item}"))
-        .collect::<Vec<_>>()
-        .join("");
-    format!("{}{}{}", befor_items, items, after_items)
+    let mut output = String::new();
+    do_forloop_to(&mut output, vector, befor_items, befor_item, after_item, after_items)
+        .expect("writing to a String does not fail");
+    output
+}
+
+/// ### do_forloop_to(out, vector, befor_items, befor_item, after_item, after_items)
+///
+/// Streaming Loop Function
+///
+/// The `do_forloop_to` function formats the items like `do_forloop`, but writes each piece into
+/// a `std::fmt::Write` sink as it goes instead of returning a new `String`.
+///
+/// ### Parameters
+/// - `out`: The sink that receives the output, such as a `String` or a `fmt::Formatter`.
+/// - `vector`, `befor_items`, `befor_item`, `after_item`, `after_items`: As for `do_forloop`.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::do_forloop_to;
+///
+/// let mut page = String::from("<h1>Fruits</h1>");
+/// do_forloop_to(&mut page, &["Apples", "Bananas"], "<ul>", "<li>", "</li>", "</ul>").unwrap();
+/// assert_eq!(page, "<h1>Fruits</h1><ul><li>Apples</li><li>Bananas</li></ul>");
+/// ```
+/// <small>End Fun Doc</small>
+pub fn do_forloop_to<W, T>(
+    out: &mut W,
+    vector: &[T],
+    befor_items: &str,
+    befor_item: &str,
+    after_item: &str,
+    after_items: &str,
+) -> std::fmt::Result
+where
+    W: std::fmt::Write + ?Sized,
+    T: std::fmt::Display,
+{
+    out.write_str(befor_items)?;
+    for item in vector {
+        write!(out, "{befor_item}{item}{after_item}")?;
+    }
+    out.write_str(after_items)
+}
+
+/// ### do_forloop_write(out, vector, befor_items, befor_item, after_item, after_items)
+///
+/// Streaming Loop Function
+///
+/// The `do_forloop_write` function formats the items like `do_forloop`, but writes them straight
+/// into a `std::io::Write` sink such as a file or a socket, without building the output in
+/// memory first.
+///
+/// ### Parameters
+/// - `out`: The sink that receives the output.
+/// - `vector`, `befor_items`, `befor_item`, `after_item`, `after_items`: As for `do_forloop`.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::do_forloop_write;
+///
+/// let mut body: Vec<u8> = Vec::new();
+/// do_forloop_write(&mut body, &[1, 2, 3], "<ol>", "<li>", "</li>", "</ol>").unwrap();
+/// assert_eq!(body, b"<ol><li>1</li><li>2</li><li>3</li></ol>");
+/// ```
+/// <small>End Fun Doc</small>
+pub fn do_forloop_write<W, T>(
+    out: &mut W,
+    vector: &[T],
+    befor_items: &str,
+    befor_item: &str,
+    after_item: &str,
+    after_items: &str,
+) -> std::io::Result<()>
+where
+    W: std::io::Write + ?Sized,
+    T: std::fmt::Display,
+{
+    sink::write_io(out, |out| do_forloop_to(out, vector, befor_items, befor_item, after_item, after_items))
+}
+
+/// ### do_json_forloop(vector)
+///
+/// JSON Array Function
+///
+/// The `do_json_forloop` function writes a slice of items as a JSON array. Unlike `do_forloop`
+/// with `"\""` prefixes and suffixes, it always produces valid JSON: strings are quoted and
+/// escaped, numbers and booleans are written unquoted, `None` becomes `null`, and nested
+/// vectors, maps, `JsonArray`s and `JsonObject`s are written as nested arrays and objects.
+/// The result is a `String`, which a `do_json!` template escapes like any other text; to insert
+/// the items there as JSON, collect them into a `JsonArray` instead.
+///
+/// ### Parameters
+/// - `vector`: A slice of items to write, where each item must implement the `ToJson` trait.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{do_json_forloop, JsonArray, JsonObject};
+/// use cans::do_json;
+///
+/// assert_eq!(do_json_forloop(&["plain", "with \"quotes\""]), r#"["plain","with \"quotes\""]"#);
+/// assert_eq!(do_json_forloop(&[1.5, 2.0]), "[1.5,2]");
+/// assert_eq!(do_json_forloop(&[Some(true), None]), "[true,null]");
+/// assert_eq!(do_json_forloop(&[vec![1, 2], vec![]]), "[[1,2],[]]");
+///
+/// let users = [JsonObject::new().field("name", "Ahmed"), JsonObject::new().field("name", "Amal")];
+/// assert_eq!(do_json_forloop(&users), r#"[{"name":"Ahmed"},{"name":"Amal"}]"#);
+///
+/// let tags: JsonArray = ["rust", "json"].iter().collect();
+/// assert_eq!(do_json!(r#"{"tags":{{tags}}}"#, tags = tags), r#"{"tags":["rust","json"]}"#);
+/// ```
+/// <small>End Fun Doc</small>
+pub fn do_json_forloop<T: ToJson>(vector: &[T]) -> String {
+    vector.to_json()
 }
 
 /// ### do_text(t)
@@ -76,9 +297,10 @@ pub fn do_forloop<T: std::fmt::Display>(
 /// This function is particularly useful in the context of generating dynamic HTML
 /// pages where text elements, such as titles, may need to be included as part
 /// of an HTML document or template. For instance, it is used in the `do_home_page`
-/// function to set the title in the `HOME_TEMPLATE` HTML document:
+/// function to set the title in the `HOME_TEMPLATE` HTML document. The `HEAD` partial is
+/// rendered first and then inserted with `Raw`, because substituted values are never expanded again:
 /// ```rust
-/// use cans::content::{do_html, do_text};
+/// use cans::content::{do_html, do_text, Raw};
 ///
 /// pub const HEAD: &str = r#"<head>
 /// <meta charset="UTF-8">
@@ -94,7 +316,8 @@ pub fn do_forloop<T: std::fmt::Display>(
 /// </html>"#;
 ///
 /// pub fn do_home_page() -> String {
-///     do_html!(HOME_TEMPLATE, HEAD = HEAD, page_title = do_text("Home"))
+///     let head = do_html!(HEAD, page_title = do_text("Home"));
+///     do_html!(HOME_TEMPLATE, HEAD = Raw(head))
 /// }
 ///
 /// pub const ABOUT_TEMPLATE: &str = r#"<!DOCTYPE html>
@@ -106,13 +329,33 @@ pub fn do_forloop<T: std::fmt::Display>(
 /// </html>"#;
 ///
 /// pub fn do_about_page() -> String {
-///     do_html!(ABOUT_TEMPLATE, HEAD = HEAD, page_title = do_text("About"))
+///     let head = do_html!(HEAD, page_title = do_text("About"));
+///     do_html!(ABOUT_TEMPLATE, HEAD = Raw(head))
 /// }
+///
+/// assert!(do_home_page().contains("<title>Home Page</title>"));
+/// assert!(do_about_page().contains("<title>About Page</title>"));
 /// ```
 ///
 /// This ensures that the text is properly formatted and owned, allowing for
 /// efficient rendering and manipulation in templates.
 ///
+/// The shared skeleton can also live in a single layout that the pages extend, so it changes in
+/// one place (see `TemplateRegistry`):
+/// ```rust
+/// use cans::content::{Escape, TemplateRegistry};
+///
+/// let mut registry = TemplateRegistry::new().with_escape(Escape::Html);
+/// registry
+///     .register("head", "<head><title>{{page_title}} Page</title></head>")
+///     .register("base", "<!DOCTYPE html><html>{{> head}}<body>{{#block content}}{{/block}}</body></html>")
+///     .register("home", "{{extends \"base\"}}{{#block content}}Home Page{{/block}}")
+///     .register("about", "{{extends \"base\"}}{{#block content}}About Page{{/block}}");
+///
+/// let home = registry.render("home", &[("page_title", "Home")]).unwrap();
+/// assert_eq!(home, "<!DOCTYPE html><html><head><title>Home Page</title></head><body>Home Page</body></html>");
+/// ```
+///
 /// <small>End Fun Doc</small>
 pub fn do_text(t: &str) -> String {
     t.to_string()
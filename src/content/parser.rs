@@ -0,0 +1,909 @@
+use std::ops::Range;
+
+use super::error::{Issue, IssueKind};
+
+/// The kind of a template tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TagKind {
+    /// `{{path}}`, `{{path!}}`, `{{path|filter:arg}}` or `{{path ? "default"}}`
+    Placeholder,
+    /// `{{#if path}}`
+    If,
+    /// `{{#unless path}}`
+    Unless,
+    /// `{{#each path as alias}}`
+    Each,
+    /// `{{> name key=value}}`
+    Partial,
+    /// `{{extends "name"}}`
+    Extends,
+    /// `{{#block name}}`
+    Block,
+    /// `{{super()}}`
+    Super,
+    /// `{{else}}`
+    Else,
+    /// `{{/if}}`
+    EndIf,
+    /// `{{/unless}}`
+    EndUnless,
+    /// `{{/each}}`
+    EndEach,
+    /// `{{/block}}`
+    EndBlock,
+    /// `{{"{{"}}`, a string written as literal text. `name_start..name_end` is the string
+    /// without the quotes.
+    Literal,
+    /// `{{! comment }}`, written nowhere.
+    Comment,
+}
+
+impl TagKind {
+    /// Returns `true` for tags whose path is looked up in the context.
+    pub(crate) const fn has_path(self) -> bool {
+        matches!(self, TagKind::Placeholder | TagKind::If | TagKind::Unless | TagKind::Each)
+    }
+
+    /// Returns `true` if this is the closing tag of an `open` section.
+    pub(crate) const fn closes(self, open: TagKind) -> bool {
+        matches!(
+            (open, self),
+            (TagKind::If, TagKind::EndIf)
+                | (TagKind::Unless, TagKind::EndUnless)
+                | (TagKind::Each, TagKind::EndEach)
+                | (TagKind::Block, TagKind::EndBlock)
+        )
+    }
+}
+
+/// The opening and closing delimiters of the tags of a template, `{{` and `}}` by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Delims<'a> {
+    pub(crate) open: &'a str,
+    pub(crate) close: &'a str,
+}
+
+impl Delims<'_> {
+    pub(crate) const DEFAULT: Delims<'static> = Delims { open: "{{", close: "}}" };
+}
+
+/// Byte offsets of a tag: `start` is the first byte of the opening delimiter and `end` is one
+/// past the closing delimiter. For tags
+/// that have a path, `path_start..path_end` is the path. `name_start..name_end` is the alias of
+/// an `{{#each}}` tag, the template name of a `{{> partial}}` or `{{extends}}` tag (without the
+/// quotes) or the name of a `{{#block}}`, `args_start..args_end` holds the `key=value`
+/// arguments of a partial and `filters_start..filters_end` the `|filter` chain of a placeholder.
+/// A placeholder is `required` when its path is followed by `!`, and when `has_default` is set,
+/// `default_start..default_end` is the text of its `? "default"` (without the quotes).
+/// `trim_before` and `trim_after` are set by the `{{- ` and ` -}}` trim markers, which remove the
+/// whitespace before and after the tag. Unused ranges are empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TagSpan {
+    pub(crate) kind: TagKind,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) path_start: usize,
+    pub(crate) path_end: usize,
+    pub(crate) name_start: usize,
+    pub(crate) name_end: usize,
+    pub(crate) args_start: usize,
+    pub(crate) args_end: usize,
+    pub(crate) filters_start: usize,
+    pub(crate) filters_end: usize,
+    pub(crate) required: bool,
+    pub(crate) has_default: bool,
+    pub(crate) default_start: usize,
+    pub(crate) default_end: usize,
+    pub(crate) trim_before: bool,
+    pub(crate) trim_after: bool,
+}
+
+impl TagSpan {
+    /// A tag of `kind` over `start..end` whose ranges are all empty.
+    const fn bare(kind: TagKind, start: usize, end: usize) -> TagSpan {
+        TagSpan {
+            kind,
+            start,
+            end,
+            path_start: end,
+            path_end: end,
+            name_start: end,
+            name_end: end,
+            args_start: end,
+            args_end: end,
+            filters_start: end,
+            filters_end: end,
+            required: false,
+            has_default: false,
+            default_start: end,
+            default_end: end,
+            trim_before: false,
+            trim_after: false,
+        }
+    }
+}
+
+/// Byte offsets of a `key=value` partial argument. The value is a path, or a string literal when
+/// `literal` is set, in which case `value_start..value_end` excludes the quotes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ArgSpan {
+    pub(crate) key_start: usize,
+    pub(crate) key_end: usize,
+    pub(crate) value_start: usize,
+    pub(crate) value_end: usize,
+    pub(crate) literal: bool,
+    /// One past the last byte of the argument, including the closing quote.
+    pub(crate) end: usize,
+}
+
+/// Byte offsets of a `|name` or `|name:arg,arg` filter of a placeholder. `args_start..args_end`
+/// is the argument list after the `:`, empty when there is none, and `end` is one past the filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FilterSpan {
+    pub(crate) name_start: usize,
+    pub(crate) name_end: usize,
+    pub(crate) args_start: usize,
+    pub(crate) args_end: usize,
+    pub(crate) end: usize,
+}
+
+/// Byte offsets of a filter argument: `start..end` excludes the quotes of a string literal, and
+/// `next` is one past the argument, including the closing quote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FilterArgSpan {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) next: usize,
+}
+
+/// Returns the offset one past the first segment of the path `start..end`, the key looked up in
+/// the context.
+pub(crate) const fn path_root_end(source: &[u8], start: usize, end: usize) -> usize {
+    let mut root_end = start;
+    while root_end < end && source[root_end] != b'.' {
+        root_end += 1;
+    }
+    root_end
+}
+
+/// Returns the first segment of the path `start..end`.
+pub(crate) const fn path_root(source: &[u8], start: usize, end: usize) -> &[u8] {
+    source.split_at(path_root_end(source, start, end)).0.split_at(start).1
+}
+
+pub(crate) const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut index = 0;
+    while index < a.len() {
+        if a[index] != b[index] {
+            return false;
+        }
+        index += 1;
+    }
+    true
+}
+
+/// Returns the line and the column in characters of byte `offset` of `source`, both counting
+/// from 1.
+pub(crate) fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Keys are made of ASCII letters, digits, `_` and `-`, so runtime keys like `site-title` work.
+const fn is_key_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-'
+}
+
+/// Returns `true` if `source` contains `prefix` at offset `at`.
+const fn starts_with(source: &[u8], at: usize, prefix: &[u8]) -> bool {
+    if at + prefix.len() > source.len() {
+        return false;
+    }
+    let mut index = 0;
+    while index < prefix.len() {
+        if source[at + index] != prefix[index] {
+            return false;
+        }
+        index += 1;
+    }
+    true
+}
+
+const fn skip_spaces(source: &[u8], mut at: usize) -> usize {
+    while at < source.len() && source[at] == b' ' {
+        at += 1;
+    }
+    at
+}
+
+/// Scans a single key starting at `at` and returns the offset one past its end, or `None` if
+/// there is no key there.
+const fn scan_key(source: &[u8], at: usize) -> Option<usize> {
+    let mut end = at;
+    while end < source.len() && is_key_byte(source[end]) {
+        end += 1;
+    }
+    if end == at { None } else { Some(end) }
+}
+
+/// Scans a path (one or more keys separated by `.`) starting at `at` and returns the offset one
+/// past its end, or `None` if there is no valid path there. The first key may start with `@`,
+/// as the loop variables `@index`, `@first` and `@last` do.
+const fn scan_path(source: &[u8], at: usize) -> Option<usize> {
+    let mut end = at;
+    let mut segment_len = 0;
+    if end < source.len() && source[end] == b'@' {
+        end += 1;
+    }
+    while end < source.len() && (is_key_byte(source[end]) || source[end] == b'.') {
+        if source[end] == b'.' {
+            if segment_len == 0 {
+                return None;
+            }
+            segment_len = 0;
+        } else {
+            segment_len += 1;
+        }
+        end += 1;
+    }
+    if segment_len == 0 { None } else { Some(end) }
+}
+
+/// Scans a template name, such as `head` or `layouts/base.html`, starting at `at`.
+const fn scan_name(source: &[u8], at: usize) -> Option<usize> {
+    let mut end = at;
+    while end < source.len() && (is_key_byte(source[end]) || source[end] == b'.' || source[end] == b'/') {
+        end += 1;
+    }
+    if end == at { None } else { Some(end) }
+}
+
+/// Scans a `key=path` or `key="text"` argument starting exactly at `at`.
+const fn scan_arg(source: &[u8], at: usize) -> Option<ArgSpan> {
+    let key_end = match scan_key(source, at) {
+        Some(key_end) => key_end,
+        None => return None,
+    };
+    if key_end >= source.len() || source[key_end] != b'=' {
+        return None;
+    }
+    let value_start = key_end + 1;
+    if value_start < source.len() && source[value_start] == b'"' {
+        let mut value_end = value_start + 1;
+        while value_end < source.len() && source[value_end] != b'"' {
+            value_end += 1;
+        }
+        if value_end == source.len() {
+            return None;
+        }
+        return Some(ArgSpan {
+            key_start: at,
+            key_end,
+            value_start: value_start + 1,
+            value_end,
+            literal: true,
+            end: value_end + 1,
+        });
+    }
+    match scan_path(source, value_start) {
+        Some(value_end) => Some(ArgSpan { key_start: at, key_end, value_start, value_end, literal: false, end: value_end }),
+        None => None,
+    }
+}
+
+/// Scans a filter argument starting at `at`: a `"quoted"` string, or a bare word such as `2`
+/// that runs up to the next space, `,`, `|` or closing delimiter.
+const fn scan_filter_arg(source: &[u8], at: usize, close: &[u8]) -> Option<FilterArgSpan> {
+    if at < source.len() && source[at] == b'"' {
+        let mut end = at + 1;
+        while end < source.len() && source[end] != b'"' {
+            end += 1;
+        }
+        if end == source.len() {
+            return None;
+        }
+        return Some(FilterArgSpan { start: at + 1, end, next: end + 1 });
+    }
+    let mut end = at;
+    while end < source.len() && !matches!(source[end], b' ' | b',' | b'|' | b'"') && !starts_with(source, end, close) {
+        end += 1;
+    }
+    if end == at { None } else { Some(FilterArgSpan { start: at, end, next: end }) }
+}
+
+/// Scans a `|name` or `|name:arg,arg` filter starting exactly at `at`.
+const fn scan_filter(source: &[u8], at: usize, close: &[u8]) -> Option<FilterSpan> {
+    if at >= source.len() || source[at] != b'|' {
+        return None;
+    }
+    let name_end = match scan_key(source, at + 1) {
+        Some(name_end) => name_end,
+        None => return None,
+    };
+    if name_end >= source.len() || source[name_end] != b':' {
+        return Some(FilterSpan { name_start: at + 1, name_end, args_start: name_end, args_end: name_end, end: name_end });
+    }
+    let args_start = name_end + 1;
+    let mut args_end = args_start;
+    loop {
+        match scan_filter_arg(source, args_end, close) {
+            Some(arg) => args_end = arg.next,
+            None => return None,
+        }
+        if args_end >= source.len() || source[args_end] != b',' {
+            break;
+        }
+        args_end += 1;
+    }
+    Some(FilterSpan { name_start: at + 1, name_end, args_start, args_end, end: args_end })
+}
+
+/// Returns the next filter of the filter chain `from..end` of a placeholder.
+pub(crate) const fn next_filter(source: &[u8], from: usize, end: usize, delims: Delims) -> Option<FilterSpan> {
+    if from >= end { None } else { scan_filter(source, from, delims.close.as_bytes()) }
+}
+
+/// Returns the next argument of the argument list `from..end` of a filter.
+pub(crate) const fn next_filter_arg(source: &[u8], from: usize, end: usize, delims: Delims) -> Option<FilterArgSpan> {
+    if from >= end {
+        return None;
+    }
+    let at = if source[from] == b',' { from + 1 } else { from };
+    scan_filter_arg(source, at, delims.close.as_bytes())
+}
+
+/// Returns the next argument of the argument list `from..end` of a partial tag.
+pub(crate) const fn next_arg(source: &[u8], from: usize, end: usize) -> Option<ArgSpan> {
+    let at = skip_spaces(source, from);
+    if at >= end { None } else { scan_arg(source, at) }
+}
+
+/// Returns `true` if `source` contains `keyword` directly followed by the end of the tag at
+/// offset `at`, or by spaces and the end of the tag when `spaces` is set.
+const fn keyword_tag(source: &[u8], at: usize, keyword: &[u8], close: &[u8], spaces: bool) -> bool {
+    starts_with(source, at, keyword) && tag_close(source, at + keyword.len(), close, spaces).is_some()
+}
+
+/// Matches the end of a tag at `at`: the closing delimiter, preceded by spaces only when
+/// `spaces` is set, or spaces followed by a `-` trim marker and the closing delimiter. Returns
+/// the offset one past the delimiter and whether the trim marker is there.
+const fn tag_close(source: &[u8], at: usize, close: &[u8], spaces: bool) -> Option<(usize, bool)> {
+    let after_spaces = skip_spaces(source, at);
+    let delimiter = if spaces { after_spaces } else { at };
+    if starts_with(source, delimiter, close) {
+        Some((delimiter + close.len(), false))
+    } else if after_spaces > at && starts_with(source, after_spaces, b"-") && starts_with(source, after_spaces + 1, close) {
+        Some((after_spaces + 1 + close.len(), true))
+    } else {
+        None
+    }
+}
+
+/// Parses the `{{! comment }}` whose text starts at `at`: it runs to the first closing
+/// delimiter, and ends with a trim marker when the text ends with ` -`.
+const fn parse_comment(source: &[u8], start: usize, at: usize, close: &[u8], trim_before: bool) -> Option<TagSpan> {
+    let mut end = at;
+    while end < source.len() && !starts_with(source, end, close) {
+        end += 1;
+    }
+    if end == source.len() {
+        return None;
+    }
+    let trim_after = end >= at + 2 && source[end - 1] == b'-' && source[end - 2] == b' ';
+    Some(TagSpan { trim_before, trim_after, ..TagSpan::bare(TagKind::Comment, start, end + close.len()) })
+}
+
+/// Parses the `{{"text"}}` literal whose text starts at `at`, right after the opening quote: it
+/// runs to the next quote, which must be followed by the closing delimiter, so the text may
+/// contain either delimiter but no quote.
+const fn parse_literal(source: &[u8], start: usize, at: usize, close: &[u8], trim_before: bool) -> Option<TagSpan> {
+    let mut quote = at;
+    while quote < source.len() && source[quote] != b'"' {
+        quote += 1;
+    }
+    if quote == source.len() {
+        return None;
+    }
+    match tag_close(source, quote + 1, close, trim_before) {
+        Some((end, trim_after)) => {
+            Some(TagSpan { name_start: at, name_end: quote, trim_before, trim_after, ..TagSpan::bare(TagKind::Literal, start, end) })
+        }
+        None => None,
+    }
+}
+
+/// Parses the tag whose opening delimiter is at `start`, or returns `None` if it is not a
+/// valid tag.
+const fn parse_tag(source: &[u8], start: usize, delims: Delims) -> Option<TagSpan> {
+    let close_delim = delims.close.as_bytes();
+    let open_end = start + delims.open.len();
+    // A `-` right after the opening delimiter and followed by spaces, or by the `!` of a
+    // comment, trims the whitespace before the tag.
+    let trim_before = starts_with(source, open_end, b"-")
+        && (starts_with(source, open_end + 1, b" ") || starts_with(source, open_end + 1, b"!"));
+    let inner = if trim_before { skip_spaces(source, open_end + 1) } else { open_end };
+    if starts_with(source, inner, b"!") {
+        return parse_comment(source, start, inner + 1, close_delim, trim_before);
+    }
+    if starts_with(source, inner, b"\"") {
+        return parse_literal(source, start, inner + 1, close_delim, trim_before);
+    }
+    let (kind, after_keyword) = if starts_with(source, inner, b"#if ") {
+        (TagKind::If, inner + 4)
+    } else if starts_with(source, inner, b"#unless ") {
+        (TagKind::Unless, inner + 8)
+    } else if starts_with(source, inner, b"#each ") {
+        (TagKind::Each, inner + 6)
+    } else if starts_with(source, inner, b">") {
+        (TagKind::Partial, inner + 1)
+    } else if starts_with(source, inner, b"extends ") {
+        (TagKind::Extends, inner + 8)
+    } else if starts_with(source, inner, b"#block ") {
+        (TagKind::Block, inner + 7)
+    } else if keyword_tag(source, inner, b"super()", close_delim, trim_before) {
+        (TagKind::Super, inner + 7)
+    } else if keyword_tag(source, inner, b"else", close_delim, trim_before) {
+        (TagKind::Else, inner + 4)
+    } else if keyword_tag(source, inner, b"/if", close_delim, trim_before) {
+        (TagKind::EndIf, inner + 3)
+    } else if keyword_tag(source, inner, b"/unless", close_delim, trim_before) {
+        (TagKind::EndUnless, inner + 7)
+    } else if keyword_tag(source, inner, b"/each", close_delim, trim_before) {
+        (TagKind::EndEach, inner + 5)
+    } else if keyword_tag(source, inner, b"/block", close_delim, trim_before) {
+        (TagKind::EndBlock, inner + 6)
+    } else {
+        (TagKind::Placeholder, inner)
+    };
+
+    let (path_start, path_end) = if kind.has_path() {
+        let path_start = skip_spaces(source, after_keyword);
+        match scan_path(source, path_start) {
+            Some(path_end) => (path_start, path_end),
+            None => return None,
+        }
+    } else {
+        (after_keyword, after_keyword)
+    };
+
+    let (name_start, name_end) = match kind {
+        TagKind::Each => {
+            let as_start = skip_spaces(source, path_end);
+            if as_start == path_end || !starts_with(source, as_start, b"as ") {
+                return None;
+            }
+            let alias_start = skip_spaces(source, as_start + 3);
+            match scan_key(source, alias_start) {
+                Some(alias_end) => (alias_start, alias_end),
+                None => return None,
+            }
+        }
+        TagKind::Partial => {
+            let name_start = skip_spaces(source, path_end);
+            match scan_name(source, name_start) {
+                Some(name_end) => (name_start, name_end),
+                None => return None,
+            }
+        }
+        TagKind::Extends => {
+            let quote = skip_spaces(source, path_end);
+            if !starts_with(source, quote, b"\"") {
+                return None;
+            }
+            match scan_name(source, quote + 1) {
+                Some(name_end) if starts_with(source, name_end, b"\"") => (quote + 1, name_end),
+                _ => return None,
+            }
+        }
+        TagKind::Block => {
+            let name_start = skip_spaces(source, path_end);
+            match scan_key(source, name_start) {
+                Some(name_end) => (name_start, name_end),
+                None => return None,
+            }
+        }
+        _ => (path_end, path_end),
+    };
+
+    // Arguments are separated from the name and from each other by spaces.
+    let args_start = if matches!(kind, TagKind::Extends) { name_end + 1 } else { name_end };
+    let mut args_end = args_start;
+    if matches!(kind, TagKind::Partial) {
+        loop {
+            let at = skip_spaces(source, args_end);
+            let trim_marker = starts_with(source, at, b"-") && starts_with(source, at + 1, close_delim);
+            if starts_with(source, at, close_delim) || trim_marker || at == args_end {
+                break;
+            }
+            match scan_arg(source, at) {
+                Some(arg) => args_end = arg.end,
+                None => return None,
+            }
+        }
+    }
+
+    // A placeholder path may be followed by a `!` required marker, then by filters without
+    // spaces, then by a `? "default"` fallback.
+    let required = matches!(kind, TagKind::Placeholder) && starts_with(source, args_end, b"!");
+    let filters_start = if required { args_end + 1 } else { args_end };
+    let mut filters_end = filters_start;
+    if matches!(kind, TagKind::Placeholder) {
+        while let Some(filter) = scan_filter(source, filters_end, close_delim) {
+            filters_end = filter.end;
+        }
+    }
+
+    let mut has_default = false;
+    let (mut default_start, mut default_end) = (filters_end, filters_end);
+    let question = skip_spaces(source, filters_end);
+    if matches!(kind, TagKind::Placeholder) && !required && starts_with(source, question, b"?") {
+        let quote = skip_spaces(source, question + 1);
+        if !starts_with(source, quote, b"\"") {
+            return None;
+        }
+        default_start = quote + 1;
+        default_end = default_start;
+        while default_end < source.len() && source[default_end] != b'"' {
+            default_end += 1;
+        }
+        if default_end == source.len() {
+            return None;
+        }
+        has_default = true;
+    }
+    let body_end = if has_default { default_end + 1 } else { filters_end };
+
+    // Section, include and layout tags, and tags that open with a trim marker, may have spaces
+    // before the closing delimiter.
+    let spaces = trim_before
+        || matches!(
+            kind,
+            TagKind::If | TagKind::Unless | TagKind::Each | TagKind::Partial | TagKind::Extends | TagKind::Block
+        );
+    match tag_close(source, body_end, close_delim, spaces) {
+        Some((end, trim_after)) => Some(TagSpan {
+            kind,
+            start,
+            end,
+            path_start,
+            path_end,
+            name_start,
+            name_end,
+            args_start,
+            args_end,
+            filters_start,
+            filters_end,
+            required,
+            has_default,
+            default_start,
+            default_end,
+            trim_before,
+            trim_after,
+        }),
+        None => None,
+    }
+}
+
+/// Finds the first tag that starts at or after `from`.
+///
+/// A tag is a `{{path}}` placeholder, where a path is one or more keys separated by `.` such as
+/// `user.address.city` or `items.0.name`, optionally marked as required with `!` as in
+/// `{{title!}}`, followed by filters such as `{{price|round:2}}` or `{{tags|join:", "|upper}}`
+/// and ended by a fallback such as `{{subtitle ? "Welcome"}}`, or one of the section tags `{{#if path}}`,
+/// `{{#unless path}}`, `{{#each path as alias}}`, `{{else}}`, `{{/if}}`, `{{/unless}}` and
+/// `{{/each}}`, a `{{> name key=value}}` partial include, or one of the layout tags
+/// `{{extends "name"}}`, `{{#block name}}`, `{{/block}}` and `{{super()}}`, a `{{! comment }}`,
+/// or a `{{"{{"}}` literal, whose quoted text is written as it is. Any tag may start with a
+/// `{{- ` or end with a ` -}}` trim marker. An opening `{{` that does not start a valid tag is
+/// skipped one byte at a time, so `{{{name}}}` finds the `{{name}}` tag. `delims` replaces `{{`
+/// and `}}` throughout. This is a `const fn` so that the compile-time checks behind the content
+/// macros scan templates exactly like the renderer.
+pub(crate) const fn next_tag(source: &[u8], from: usize, delims: Delims) -> Option<TagSpan> {
+    let open = delims.open.as_bytes();
+    let mut cursor = from;
+    while cursor < source.len() {
+        if starts_with(source, cursor, open)
+            && let Some(tag) = parse_tag(source, cursor, delims)
+        {
+            return Some(tag);
+        }
+        cursor += 1;
+    }
+    None
+}
+
+/// Returns `true` if a tag of `source` looks up `key`: the path of a placeholder or section,
+/// or a partial argument given as a path, starts with `key`.
+pub(crate) const fn references_key(source: &[u8], key: &[u8], delims: Delims) -> bool {
+    let mut from = 0;
+    while let Some(tag) = next_tag(source, from, delims) {
+        from = tag.end;
+        if tag.kind.has_path() && bytes_eq(path_root(source, tag.path_start, tag.path_end), key) {
+            return true;
+        }
+        if matches!(tag.kind, TagKind::Partial) {
+            let mut arg_from = tag.args_start;
+            while let Some(arg) = next_arg(source, arg_from, tag.args_end) {
+                if !arg.literal && bytes_eq(path_root(source, arg.value_start, arg.value_end), key) {
+                    return true;
+                }
+                arg_from = arg.end;
+            }
+        }
+    }
+    false
+}
+
+/// A parsed piece of a template, stored as byte ranges into the template source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Node {
+    /// Static text written unchanged.
+    Text(Range<usize>),
+    /// A placeholder slot: `path` is the dotted path, `filters` the filters applied to its value
+    /// in order and `span` the whole `{{path|filter}}` tag. `default` is the text written when
+    /// the value is missing or null, and a `required` placeholder reports a null value as missing.
+    Placeholder {
+        path: Range<usize>,
+        filters: Vec<FilterCall>,
+        required: bool,
+        default: Option<Range<usize>>,
+        span: Range<usize>,
+    },
+    /// An `{{#if path}}` section, or an `{{#unless path}}` section when `negated` is set.
+    /// `then` is rendered when the value at `path` is truthy (falsy when negated) and
+    /// `otherwise` (after `{{else}}`) in the other case.
+    Section {
+        negated: bool,
+        path: Range<usize>,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    /// An `{{#each path as alias}}` loop: `body` is rendered once per item with the item bound to
+    /// `alias`, and `otherwise` (after `{{else}}`) when there are no items. `span` is the opening tag.
+    Each {
+        path: Range<usize>,
+        alias: Range<usize>,
+        span: Range<usize>,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    /// A `{{> name key=value}}` include of the registered template `name`, rendered with `args`
+    /// layered over the current context. `span` is the whole tag.
+    Partial {
+        name: Range<usize>,
+        args: Vec<PartialArg>,
+        span: Range<usize>,
+    },
+    /// An `{{extends "name"}}` tag at the top level of a template: the template is rendered as
+    /// the registered layout `name` with its blocks overridden. Written as literal text when the
+    /// layout cannot be resolved. `span` is the whole tag.
+    Extends { name: Range<usize>, span: Range<usize> },
+    /// A `{{#block name}}` that a template extending this one can override. `body` is the
+    /// default content.
+    Block { name: Range<usize>, body: Vec<Node> },
+    /// A `{{super()}}` tag inside a block, rendering the content of the overridden block.
+    Super,
+}
+
+/// A `key=value` argument of a partial include.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PartialArg {
+    pub(crate) key: Range<usize>,
+    /// A path looked up in the including context, or the text of a string literal.
+    pub(crate) value: Range<usize>,
+    pub(crate) literal: bool,
+}
+
+/// A `|name:arg,arg` filter of a placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FilterCall {
+    pub(crate) name: Range<usize>,
+    /// The arguments, without the quotes of string literals.
+    pub(crate) args: Vec<Range<usize>>,
+}
+
+/// A section whose closing tag has not been reached yet.
+struct OpenSection {
+    tag: TagSpan,
+    /// The `{{else}}` tag, once seen.
+    else_tag: Option<Range<usize>>,
+    then: Vec<Node>,
+    otherwise: Vec<Node>,
+}
+
+impl OpenSection {
+    fn nodes(&mut self) -> &mut Vec<Node> {
+        if self.else_tag.is_some() { &mut self.otherwise } else { &mut self.then }
+    }
+}
+
+/// Parses `source` into a tree of nodes in a single pass.
+///
+/// Tags that do not fit the section structure, such as an `{{/if}}` without an `{{#if}}`, an
+/// `{{#if}}` that is never closed, a `{{super()}}` outside of a block or an `{{extends}}` that
+/// is not the first one at the top level, are kept as literal text and reported as issues, as
+/// is text that opens with `{{` without forming a valid tag.
+pub(crate) fn parse(source: &str, delims: Delims) -> (Vec<Node>, Vec<Issue>) {
+    let mut root = Vec::new();
+    let mut open: Vec<OpenSection> = Vec::new();
+    let mut issues = Vec::new();
+    let mut text_start = 0;
+
+    while let Some(tag) = next_tag(source.as_bytes(), text_start, delims) {
+        let text_end = if tag.trim_before { trim_end(source, text_start, tag.start) } else { tag.start };
+        if text_start < text_end {
+            malformed(source, text_start..text_end, delims, &mut issues);
+            current(&mut root, &mut open).push(Node::Text(text_start..text_end));
+        }
+        text_start = if tag.trim_after { trim_start(source, tag.end) } else { tag.end };
+
+        match tag.kind {
+            TagKind::Placeholder => {
+                let mut filters = Vec::new();
+                let mut from = tag.filters_start;
+                while let Some(filter) = next_filter(source.as_bytes(), from, tag.filters_end, delims) {
+                    let mut args = Vec::new();
+                    let mut arg_from = filter.args_start;
+                    while let Some(arg) = next_filter_arg(source.as_bytes(), arg_from, filter.args_end, delims) {
+                        args.push(arg.start..arg.end);
+                        arg_from = arg.next;
+                    }
+                    filters.push(FilterCall { name: filter.name_start..filter.name_end, args });
+                    from = filter.end;
+                }
+                current(&mut root, &mut open).push(Node::Placeholder {
+                    path: tag.path_start..tag.path_end,
+                    filters,
+                    required: tag.required,
+                    default: tag.has_default.then_some(tag.default_start..tag.default_end),
+                    span: tag.start..tag.end,
+                });
+            }
+            TagKind::Partial => {
+                let mut args = Vec::new();
+                let mut from = tag.args_start;
+                while let Some(arg) = next_arg(source.as_bytes(), from, tag.args_end) {
+                    args.push(PartialArg {
+                        key: arg.key_start..arg.key_end,
+                        value: arg.value_start..arg.value_end,
+                        literal: arg.literal,
+                    });
+                    from = arg.end;
+                }
+                current(&mut root, &mut open).push(Node::Partial {
+                    name: tag.name_start..tag.name_end,
+                    args,
+                    span: tag.start..tag.end,
+                });
+            }
+            TagKind::Literal => current(&mut root, &mut open).push(Node::Text(tag.name_start..tag.name_end)),
+            TagKind::Comment => {}
+            TagKind::Extends => {
+                let first = open.is_empty() && !root.iter().any(|node| matches!(node, Node::Extends { .. }));
+                if first {
+                    root.push(Node::Extends { name: tag.name_start..tag.name_end, span: tag.start..tag.end });
+                } else {
+                    unexpected(source, tag, current(&mut root, &mut open), &mut issues);
+                }
+            }
+            TagKind::Super => {
+                if open.iter().any(|section| section.tag.kind == TagKind::Block) {
+                    current(&mut root, &mut open).push(Node::Super);
+                } else {
+                    unexpected(source, tag, current(&mut root, &mut open), &mut issues);
+                }
+            }
+            TagKind::If | TagKind::Unless | TagKind::Each | TagKind::Block => open.push(OpenSection {
+                tag,
+                else_tag: None,
+                then: Vec::new(),
+                otherwise: Vec::new(),
+            }),
+            TagKind::Else => match open.last_mut() {
+                Some(section) if section.else_tag.is_none() && section.tag.kind != TagKind::Block => {
+                    section.else_tag = Some(tag.start..tag.end)
+                }
+                _ => unexpected(source, tag, current(&mut root, &mut open), &mut issues),
+            },
+            TagKind::EndIf | TagKind::EndUnless | TagKind::EndEach | TagKind::EndBlock => {
+                if open.last().is_some_and(|section| tag.kind.closes(section.tag.kind)) {
+                    let section = open.pop().expect("checked above");
+                    let open_tag = section.tag;
+                    let node = match open_tag.kind {
+                        TagKind::Each => Node::Each {
+                            path: open_tag.path_start..open_tag.path_end,
+                            alias: open_tag.name_start..open_tag.name_end,
+                            span: open_tag.start..open_tag.end,
+                            body: section.then,
+                            otherwise: section.otherwise,
+                        },
+                        TagKind::Block => Node::Block {
+                            name: open_tag.name_start..open_tag.name_end,
+                            body: section.then,
+                        },
+                        _ => Node::Section {
+                            negated: open_tag.kind == TagKind::Unless,
+                            path: open_tag.path_start..open_tag.path_end,
+                            then: section.then,
+                            otherwise: section.otherwise,
+                        },
+                    };
+                    current(&mut root, &mut open).push(node);
+                } else {
+                    unexpected(source, tag, current(&mut root, &mut open), &mut issues);
+                }
+            }
+        }
+    }
+
+    if text_start < source.len() {
+        malformed(source, text_start..source.len(), delims, &mut issues);
+        current(&mut root, &mut open).push(Node::Text(text_start..source.len()));
+    }
+
+    // Unclosed sections are flattened back into literal text around their contents.
+    while let Some(section) = open.pop() {
+        let span = section.tag.start..section.tag.end;
+        issues.push(Issue::new(
+            IssueKind::UnclosedSection { tag: source[span.clone()].to_string() },
+            span.clone(),
+            source,
+        ));
+        let parent = current(&mut root, &mut open);
+        parent.push(Node::Text(span));
+        parent.extend(section.then);
+        if let Some(else_tag) = section.else_tag {
+            parent.push(Node::Text(else_tag));
+        }
+        parent.extend(section.otherwise);
+    }
+
+    issues.sort_by_key(|issue| issue.span.start);
+    (root, issues)
+}
+
+/// Returns the offset where the whitespace at the end of `start..end` begins.
+fn trim_end(source: &str, start: usize, end: usize) -> usize {
+    start + source[start..end].trim_end_matches(|c: char| c.is_ascii_whitespace()).len()
+}
+
+/// Returns the offset of the first non-whitespace character at or after `at`.
+fn trim_start(source: &str, at: usize) -> usize {
+    source.len() - source[at..].trim_start_matches(|c: char| c.is_ascii_whitespace()).len()
+}
+
+/// Returns the node list that the next node is added to.
+fn current<'a>(root: &'a mut Vec<Node>, open: &'a mut [OpenSection]) -> &'a mut Vec<Node> {
+    match open.last_mut() {
+        Some(section) => section.nodes(),
+        None => root,
+    }
+}
+
+/// Keeps a tag that does not fit the section structure as literal text and reports it.
+fn unexpected(source: &str, tag: TagSpan, nodes: &mut Vec<Node>, issues: &mut Vec<Issue>) {
+    let span = tag.start..tag.end;
+    issues.push(Issue::new(IssueKind::UnexpectedTag { tag: source[span.clone()].to_string() }, span.clone(), source));
+    nodes.push(Node::Text(span));
+}
+
+/// Reports every opening delimiter in the static text `text`: it does not start a valid tag,
+/// or the scanner would have found one there. The reported tag runs to the next closing
+/// delimiter, or to the end of the line when there is none.
+fn malformed(source: &str, text: Range<usize>, delims: Delims, issues: &mut Vec<Issue>) {
+    let mut from = text.start;
+    while let Some(offset) = source[from..text.end].find(delims.open) {
+        let start = from + offset;
+        let rest = &source[start..text.end];
+        let end = match rest[delims.open.len()..].find(delims.close) {
+            Some(close) => start + delims.open.len() + close + delims.close.len(),
+            None => start + rest.find('\n').unwrap_or(rest.len()),
+        };
+        issues.push(Issue::new(IssueKind::MalformedTag { tag: source[start..end].to_string() }, start..end, source));
+        from = end;
+    }
+}
@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use super::parser::{line_column, next_arg, next_filter, next_tag, Delims, TagKind};
+
+/// ### Placeholder
+///
+/// Template Reference
+///
+/// A `Placeholder` describes one tag of a template that refers to a value or to another
+/// template, as returned by `placeholders` and `Template::placeholders`: its kind, the name it
+/// refers to and where it is written.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{placeholders, PlaceholderKind};
+///
+/// let found = placeholders("<h1>{{title!}}</h1>\n{{> footer year=site.year}}");
+///
+/// assert_eq!(found[0].name, "title");
+/// assert_eq!(found[0].span, 4..14);
+/// assert!(matches!(found[0].kind, PlaceholderKind::Value { required: true, .. }));
+/// assert_eq!((found[1].line, found[1].column), (2, 1));
+/// assert_eq!(
+///     found[1].kind,
+///     PlaceholderKind::Partial { args: vec![("year".to_string(), "site.year".to_string())] }
+/// );
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    /// What the tag is.
+    pub kind: PlaceholderKind,
+    /// The dotted path of a value, section or loop, or the name of a partial, layout or block.
+    pub name: String,
+    /// The byte range of the whole tag in the template source.
+    pub span: Range<usize>,
+    /// The line of the tag, counting from 1.
+    pub line: usize,
+    /// The column of the tag in characters, counting from 1.
+    pub column: usize,
+}
+
+/// The kind of a `Placeholder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PlaceholderKind {
+    /// A `{{path}}` value.
+    Value {
+        /// Whether the value is marked as required, as in `{{title!}}`.
+        required: bool,
+        /// The fallback text of `{{subtitle ? "Welcome"}}`.
+        default: Option<String>,
+        /// The names of the filters applied to the value, in order.
+        filters: Vec<String>,
+    },
+    /// An `{{#if path}}` section, or an `{{#unless path}}` section when `negated` is set.
+    Section {
+        /// Whether this is an `{{#unless}}` section.
+        negated: bool,
+    },
+    /// An `{{#each path as alias}}` loop.
+    Loop {
+        /// The name each item is bound to.
+        alias: String,
+    },
+    /// A `{{> name}}` partial include.
+    Partial {
+        /// The key and the path of every argument given as a path, such as `title=post.title`.
+        /// String literal arguments refer to no value and are not listed.
+        args: Vec<(String, String)>,
+    },
+    /// An `{{extends "name"}}` layout.
+    Layout,
+    /// A `{{#block name}}` that extending templates can override.
+    Block,
+}
+
+/// ### placeholders(template)
+///
+/// Template Introspection Function
+///
+/// The `placeholders` function lists the values, sections, loops, partials, layouts and blocks
+/// that `template` refers to, in source order and with their positions. It scans the template
+/// with the same scanner as the renderer, so it sees exactly the tags that rendering fills.
+/// Use it to generate forms for the values of a template, to check that translations cover
+/// every key, or to document a template library. Paths inside an `{{#each}}` loop are listed
+/// as written, so they may start with the loop alias or a loop variable such as `@index`.
+///
+/// ### Parameters
+/// - `template`: The template source (e.g., `"<h1>{{title}}</h1>"`).
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{placeholders, PlaceholderKind};
+///
+/// pub const HOME_TEMPLATE: &str = r#"{{extends "base"}}
+/// {{#block content}}
+///   <h1>{{title|upper}}</h1>
+///   {{#each posts as post}}<p>{{post.title ? "Untitled"}}</p>{{/each}}
+/// {{/block}}"#;
+///
+/// let found = placeholders(HOME_TEMPLATE);
+/// let names: Vec<&str> = found.iter().map(|found| found.name.as_str()).collect();
+/// assert_eq!(names, ["base", "content", "title", "posts", "post.title"]);
+///
+/// // Keys a form or a translation file has to provide.
+/// let keys: Vec<&str> = found
+///     .iter()
+///     .filter(|found| matches!(found.kind, PlaceholderKind::Value { default: None, .. }))
+///     .map(|found| found.name.as_str())
+///     .collect();
+/// assert_eq!(keys, ["title"]);
+/// ```
+/// <small>End Fun Doc</small>
+pub fn placeholders(template: &str) -> Vec<Placeholder> {
+    scan(template, Delims::DEFAULT)
+}
+
+/// Lists the references of `template`, whose tags use `delims`.
+pub(crate) fn scan(template: &str, delims: Delims) -> Vec<Placeholder> {
+    let source = template.as_bytes();
+    let text = |range: Range<usize>| template[range].to_string();
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(tag) = next_tag(source, from, delims) {
+        from = tag.end;
+        let path = tag.path_start..tag.path_end;
+        let name = tag.name_start..tag.name_end;
+        let (kind, name) = match tag.kind {
+            TagKind::Placeholder => {
+                let mut filters = Vec::new();
+                let mut filter_from = tag.filters_start;
+                while let Some(filter) = next_filter(source, filter_from, tag.filters_end, delims) {
+                    filters.push(text(filter.name_start..filter.name_end));
+                    filter_from = filter.end;
+                }
+                let default = tag.has_default.then(|| text(tag.default_start..tag.default_end));
+                (PlaceholderKind::Value { required: tag.required, default, filters }, path)
+            }
+            TagKind::If | TagKind::Unless => (PlaceholderKind::Section { negated: tag.kind == TagKind::Unless }, path),
+            TagKind::Each => (PlaceholderKind::Loop { alias: text(name) }, path),
+            TagKind::Partial => {
+                let mut args = Vec::new();
+                let mut arg_from = tag.args_start;
+                while let Some(arg) = next_arg(source, arg_from, tag.args_end) {
+                    if !arg.literal {
+                        args.push((text(arg.key_start..arg.key_end), text(arg.value_start..arg.value_end)));
+                    }
+                    arg_from = arg.end;
+                }
+                (PlaceholderKind::Partial { args }, name)
+            }
+            TagKind::Extends => (PlaceholderKind::Layout, name),
+            TagKind::Block => (PlaceholderKind::Block, name),
+            _ => continue,
+        };
+        let (line, column) = line_column(template, tag.start);
+        found.push(Placeholder { kind, name: text(name), span: tag.start..tag.end, line, column });
+    }
+    found
+}
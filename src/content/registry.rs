@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use super::context::Context;
+use super::delimiters::Delimiters;
+use super::error::{Issue, IssueKind, RenderError};
+use super::escape::Escape;
+use super::filter::Filter;
+use super::render::Renderer;
+use super::sink;
+use super::template::Template;
+use super::value::Value;
+
+/// ### TemplateRegistry
+///
+/// Named Template Registry
+///
+/// A `TemplateRegistry` stores templates by name so that they can include each other with
+/// `{{> name}}` partial tags and extend each other's layouts with `{{extends "name"}}`. A
+/// partial sees the context of the template that includes it, plus its own `key=path` or
+/// `key="text"` arguments, which take precedence:
+/// `{{> card title=post.title}}` renders the `card` template with `{{title}}` bound to
+/// `post.title`. String literals are part of the template and are inserted without escaping.
+///
+/// Includes are resolved recursively. A partial that includes itself, directly or through
+/// other partials, a layout that extends itself, and includes nested deeper than the depth
+/// limit (32 by default) are not rendered; their tags are written as literal text and
+/// reported by `render_strict`, as are includes of templates that are not registered.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{Escape, TemplateRegistry};
+///
+/// let mut registry = TemplateRegistry::new().with_escape(Escape::Html);
+/// registry
+///     .register("head", "<head><title>{{title}}</title></head>")
+///     .register("card", "<div class=\"card\"><h2>{{title}}</h2>{{> footer}}</div>")
+///     .register("footer", "<small>{{site}}</small>")
+///     .register("home", "<!DOCTYPE html><html>{{> head}}<body>{{> card title=post.title}}</body></html>");
+///
+/// #[derive(cans::Context)]
+/// struct Post {
+///     title: &'static str,
+/// }
+///
+/// #[derive(cans::Context)]
+/// struct Home {
+///     title: &'static str,
+///     site: &'static str,
+///     post: Post,
+/// }
+///
+/// let home = Home { title: "Home", site: "CANS", post: Post { title: "Fish & Chips" } };
+/// assert_eq!(
+///     registry.render("home", &home).unwrap(),
+///     "<!DOCTYPE html><html><head><title>Home</title></head>\
+///      <body><div class=\"card\"><h2>Fish &amp; Chips</h2><small>CANS</small></div></body></html>"
+/// );
+/// assert_eq!(registry.render("missing", &home), None);
+/// ```
+///
+/// #### Example: Include cycles
+/// ```rust
+/// use cans::content::{IssueKind, TemplateRegistry};
+///
+/// let mut registry = TemplateRegistry::new();
+/// registry.register("a", "A{{> b}}").register("b", "B{{> a}}");
+///
+/// assert_eq!(registry.render("a", &[("x", "")]).unwrap(), "AB{{> a}}");
+///
+/// let error = registry.render_strict("a", &[("x", "")]).unwrap_err();
+/// assert_eq!(error.issues()[0].template.as_deref(), Some("b"));
+/// assert!(matches!(error.issues()[0].kind, IssueKind::PartialCycle { .. }));
+/// ```
+///
+/// #### Example: Layouts
+///
+/// A template that starts with `{{extends "base"}}` is rendered as the registered `base`
+/// layout, with each `{{#block name}}...{{/block}}` of the layout replaced by the template's own
+/// block of the same name. Blocks it does not override keep the layout's content, and
+/// `{{super()}}` inside an override renders the layout's content for that block. Layouts can
+/// extend other layouts; everything outside the blocks of an extending template is ignored.
+/// ```rust
+/// use cans::content::TemplateRegistry;
+///
+/// let mut registry = TemplateRegistry::new();
+/// registry
+///     .register(
+///         "base",
+///         "<html><head>{{#block head}}<title>{{title}}</title>{{/block}}</head>\
+///          <body>{{#block content}}{{/block}}</body></html>",
+///     )
+///     .register("page", "{{extends \"base\"}}{{#block content}}<main>{{#block main}}{{/block}}</main>{{/block}}")
+///     .register(
+///         "home",
+///         "{{extends \"page\"}}\
+///          {{#block head}}{{super()}}<link rel=\"stylesheet\" href=\"home.css\">{{/block}}\
+///          {{#block main}}Welcome to {{title}}{{/block}}",
+///     );
+///
+/// assert_eq!(
+///     registry.render("home", &[("title", "CANS")]).unwrap(),
+///     "<html><head><title>CANS</title><link rel=\"stylesheet\" href=\"home.css\"></head>\
+///      <body><main>Welcome to CANS</main></body></html>"
+/// );
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone)]
+pub struct TemplateRegistry {
+    templates: HashMap<String, Template>,
+    filters: HashMap<String, Filter>,
+    escape: Escape,
+    delimiters: Delimiters,
+    indent: bool,
+    max_depth: usize,
+}
+
+impl Default for TemplateRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TemplateRegistry {
+    /// Creates an empty registry whose templates insert values unchanged.
+    pub fn new() -> Self {
+        TemplateRegistry {
+            templates: HashMap::new(),
+            filters: HashMap::new(),
+            escape: Escape::None,
+            delimiters: Delimiters::default(),
+            indent: false,
+            max_depth: 32,
+        }
+    }
+
+    /// Sets the escaping mode of the templates registered from source with `register` afterwards.
+    pub fn with_escape(mut self, escape: Escape) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Sets the tag delimiters of the templates registered from source with `register`
+    /// afterwards. See `Delimiters`.
+    pub fn with_delimiters(mut self, delimiters: Delimiters) -> Self {
+        self.delimiters = delimiters;
+        self
+    }
+
+    /// Sets whether the templates registered from source with `register` afterwards indent
+    /// multi-line values to the column of their placeholder. See `Template::with_indent`.
+    pub fn with_indent(mut self, indent: bool) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets how deeply partials may be nested.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns how deeply partials may be nested.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Parses `source` and stores it under `name` with the registry's escaping mode, delimiters
+    /// and indentation mode, replacing any template already registered under that name.
+    pub fn register(&mut self, name: impl Into<String>, source: impl Into<String>) -> &mut Self {
+        let template = Template::new(source)
+            .with_escape(self.escape)
+            .with_delimiters(self.delimiters.clone())
+            .with_indent(self.indent);
+        self.register_template(name, template)
+    }
+
+    /// Stores an already parsed `template` under `name`, keeping its own escaping mode,
+    /// delimiters and indentation mode.
+    pub fn register_template(&mut self, name: impl Into<String>, template: Template) -> &mut Self {
+        self.templates.insert(name.into(), template);
+        self
+    }
+
+    /// Registers `filter` under `name` for the `{{path|name}}` placeholders of every template
+    /// rendered through this registry, replacing a built-in filter of the same name. The filter
+    /// receives the value and the filter's arguments, and returns the new value or a message
+    /// explaining why it cannot be applied, which `render_strict` reports.
+    ///
+    /// ```rust
+    /// use cans::content::{TemplateRegistry, Value};
+    ///
+    /// let mut registry = TemplateRegistry::new();
+    /// registry
+    ///     .register_filter("currency", |value, args| match value {
+    ///         Value::Int(cents) => Ok(Value::from(format!("{}{}.{:02}", args.first().unwrap_or(&"$"), cents / 100, cents % 100))),
+    ///         value => Err(format!("{value} is not an amount in cents")),
+    ///     })
+    ///     .register("price", "{{total|currency}} / {{total|currency:\"€\"}}");
+    ///
+    /// assert_eq!(registry.render("price", &[("total", 1250)]).unwrap(), "$12.50 / €12.50");
+    /// ```
+    pub fn register_filter<F>(&mut self, name: impl Into<String>, filter: F) -> &mut Self
+    where
+        F: for<'a> Fn(Value<'a>, &[&str]) -> Result<Value<'a>, String> + Send + Sync + 'static,
+    {
+        self.filters.insert(name.into(), Filter::new(filter));
+        self
+    }
+
+    /// Returns the template registered under `name`.
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    /// Returns `true` if a template is registered under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+
+    /// Returns the names of the registered templates, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+
+    /// Removes the template registered under `name` and returns it.
+    pub fn remove(&mut self, name: &str) -> Option<Template> {
+        self.templates.remove(name)
+    }
+
+    /// Renders the template registered under `name` with its partials, or returns `None` if
+    /// there is no such template.
+    pub fn render<C: Context + ?Sized>(&self, name: &str, context: &C) -> Option<String> {
+        let (name, template) = self.entry(name)?;
+        let mut output = String::with_capacity(template.source().len());
+        Renderer::new(Some(self))
+            .render(&mut output, template, Some(name), &context)
+            .expect("writing to a String does not fail");
+        Some(output)
+    }
+
+    /// Renders the template registered under `name` with its partials into `out`, or returns
+    /// `None` if there is no such template.
+    pub fn render_to<W, C>(&self, name: &str, out: &mut W, context: &C) -> Option<fmt::Result>
+    where
+        W: fmt::Write + ?Sized,
+        C: Context + ?Sized,
+    {
+        let (name, template) = self.entry(name)?;
+        Some(Renderer::new(Some(self)).render(out, template, Some(name), &context))
+    }
+
+    /// Renders the template registered under `name` with its partials straight into an
+    /// `io::Write` sink, or returns `None` if there is no such template.
+    ///
+    /// ```rust
+    /// use std::io::Write;
+    /// use cans::content::TemplateRegistry;
+    ///
+    /// let mut registry = TemplateRegistry::new();
+    /// registry.register("row", "<tr><td>{{id}}</td></tr>").register("report", "<table>{{#each ids as id}}{{> row}}{{/each}}</table>");
+    ///
+    /// let mut out = std::io::BufWriter::new(Vec::new());
+    /// registry.write_to("report", &mut out, &[("ids", vec![1, 2])]).unwrap().unwrap();
+    /// out.flush().unwrap();
+    /// assert_eq!(out.get_ref(), b"<table><tr><td>1</td></tr><tr><td>2</td></tr></table>");
+    /// ```
+    pub fn write_to<W, C>(&self, name: &str, out: &mut W, context: &C) -> Option<io::Result<()>>
+    where
+        W: io::Write + ?Sized,
+        C: Context + ?Sized,
+    {
+        let (name, template) = self.entry(name)?;
+        Some(sink::write_io(out, |out| Renderer::new(Some(self)).render(out, template, Some(name), &context)))
+    }
+
+    /// Renders the template registered under `name` like `render`, but returns a `RenderError`
+    /// listing every problem found in it or in its partials.
+    pub fn render_strict<C: Context + ?Sized>(&self, name: &str, context: &C) -> Result<String, RenderError> {
+        let Some((name, template)) = self.entry(name) else {
+            let kind = IssueKind::UnknownTemplate { name: name.to_string() };
+            return Err(RenderError::new(vec![Issue::new(kind, 0..0, "")]));
+        };
+        let mut output = String::with_capacity(template.source().len());
+        let mut renderer = Renderer::new(Some(self));
+        renderer
+            .render(&mut output, template, Some(name), &context)
+            .expect("writing to a String does not fail");
+        renderer.finish().map(|()| output)
+    }
+
+    /// Returns the custom filter registered under `name`.
+    pub(crate) fn filter(&self, name: &str) -> Option<&Filter> {
+        self.filters.get(name)
+    }
+
+    /// Returns the registered name and the template for `name`.
+    pub(crate) fn entry(&self, name: &str) -> Option<(&str, &Template)> {
+        self.templates.get_key_value(name).map(|(name, template)| (name.as_str(), template))
+    }
+}
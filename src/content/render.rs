@@ -0,0 +1,500 @@
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use super::context::Context;
+use super::error::{Issue, IssueKind, RenderError};
+use super::filter;
+use super::delimiters::Delimiters;
+use super::parser::{self, FilterCall, Node};
+use super::registry::TemplateRegistry;
+use super::sink::{Indent, Output};
+use super::template::Template;
+use super::value::{TemplateValue, Value};
+
+/// Walks the nodes of a template and writes them out, resolving partials through an optional
+/// registry and collecting the issues found on the way.
+pub(crate) struct Renderer<'r> {
+    registry: Option<&'r TemplateRegistry>,
+    /// The registered templates being rendered through partials and layouts, outermost first.
+    stack: Vec<&'r str>,
+    /// The number of partials being rendered.
+    depth: usize,
+    /// The template being rendered followed by the layouts it extends, most derived first.
+    layouts: Vec<Frame<'r>>,
+    /// The blocks being rendered, innermost last, with the index in `layouts` of the template
+    /// whose definition is used; `{{super()}}` renders the next definition up the chain.
+    blocks: Vec<(&'r str, usize)>,
+    /// Set when writing a template source in which only the supplied values are resolved.
+    staging: bool,
+    issues: Vec<Issue>,
+}
+
+/// A template being rendered and the name it is registered under, if any.
+#[derive(Clone, Copy)]
+struct Frame<'r> {
+    template: &'r Template,
+    name: Option<&'r str>,
+}
+
+impl<'r> Renderer<'r> {
+    pub(crate) fn new(registry: Option<&'r TemplateRegistry>) -> Self {
+        Renderer {
+            registry,
+            stack: Vec::new(),
+            depth: 0,
+            layouts: Vec::new(),
+            blocks: Vec::new(),
+            staging: false,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Makes the renderer write a template source instead of the rendered text: the values the
+    /// context supplies are written as literal text, and the tags whose values it does not
+    /// supply are written back as tags, for a later stage to resolve.
+    pub(crate) fn staging(mut self) -> Self {
+        self.staging = true;
+        self
+    }
+
+    /// Renders `template`, which is registered under `name` if it has one, into `out`.
+    pub(crate) fn render<W>(&mut self, out: &mut W, template: &'r Template, name: Option<&'r str>, context: &dyn Context) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+    {
+        if !self.staging {
+            return self.render_into(&mut Output::new(out), template, name, context);
+        }
+        let mut output = Output::literal(out, template.delimiters());
+        self.render_into(&mut output, template, name, context)?;
+        output.flush()
+    }
+
+    /// Renders `template` like `render`. When the template extends a layout, the outermost
+    /// layout is rendered with the blocks of the templates below it.
+    fn render_into<W>(&mut self, out: &mut Output<'_, W>, template: &'r Template, name: Option<&'r str>, context: &dyn Context) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+    {
+        let mut layouts = vec![Frame { template, name }];
+        self.add_parse_issues(layouts[0]);
+        while let Some(frame) = self.parent_layout(*layouts.last().expect("never empty"), &layouts) {
+            self.add_parse_issues(frame);
+            layouts.push(frame);
+        }
+
+        let names: Vec<&'r str> = layouts.iter().filter_map(|frame| frame.name).collect();
+        self.stack.extend(&names);
+        let outer_layouts = std::mem::replace(&mut self.layouts, layouts);
+        let outer_blocks = std::mem::take(&mut self.blocks);
+
+        let root = *self.layouts.last().expect("never empty");
+        let result = self.write_nodes(out, root, root.template.nodes(), context);
+
+        self.layouts = outer_layouts;
+        self.blocks = outer_blocks;
+        self.stack.truncate(self.stack.len() - names.len());
+        result
+    }
+
+    /// Returns the layout that `frame` extends, reporting layouts that are not registered or
+    /// that are already being rendered.
+    fn parent_layout(&mut self, frame: Frame<'r>, layouts: &[Frame<'r>]) -> Option<Frame<'r>> {
+        let source = frame.template.source();
+        let (name, span) = frame.template.nodes().iter().find_map(|node| match node {
+            Node::Extends { name, span } => Some((&source[name.clone()], span.clone())),
+            _ => None,
+        })?;
+        let tag = &source[span.clone()];
+
+        let Some((registered, template)) = self.registry.and_then(|registry| registry.entry(name)) else {
+            let kind = IssueKind::UnknownPartial { tag: tag.to_string(), name: name.to_string() };
+            self.report(frame, kind, span);
+            return None;
+        };
+        if self.stack.contains(&registered) || layouts.iter().any(|layout| layout.name == Some(registered)) {
+            let kind = IssueKind::PartialCycle { tag: tag.to_string(), name: name.to_string() };
+            self.report(frame, kind, span);
+            return None;
+        }
+        Some(Frame { template, name: Some(registered) })
+    }
+
+    fn add_parse_issues(&mut self, frame: Frame<'r>) {
+        self.issues.extend(frame.template.issues().iter().map(|issue| Issue {
+            template: frame.name.map(str::to_string),
+            ..issue.clone()
+        }));
+    }
+
+    /// Records a problem with a tag of the template in `frame`.
+    fn report(&mut self, frame: Frame<'r>, kind: IssueKind, span: Range<usize>) {
+        let template = frame.name.map(str::to_string);
+        self.issues.push(Issue { template, ..Issue::new(kind, span, frame.template.source()) });
+    }
+
+    /// Returns the issues found, once each, grouped by template in the order the templates were
+    /// rendered and in source order within each template.
+    pub(crate) fn finish(self) -> Result<(), RenderError> {
+        let mut issues: Vec<Issue> = Vec::with_capacity(self.issues.len());
+        for issue in self.issues {
+            if !issues.contains(&issue) {
+                issues.push(issue);
+            }
+        }
+        if issues.is_empty() {
+            return Ok(());
+        }
+
+        let mut templates: Vec<Option<String>> = Vec::new();
+        for issue in &issues {
+            if !templates.contains(&issue.template) {
+                templates.push(issue.template.clone());
+            }
+        }
+        issues.sort_by_key(|issue| (templates.iter().position(|name| *name == issue.template), issue.span.start));
+        Err(RenderError::new(issues))
+    }
+
+    /// Writes `nodes` of `template` into `out`. Tags that cannot be rendered are written as
+    /// their original text and reported. The context is a trait object because loops and
+    /// partials wrap it in a new scope at every level of nesting.
+    fn write_nodes<W>(&mut self, out: &mut Output<'_, W>, frame: Frame<'r>, nodes: &'r [Node], context: &dyn Context) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+    {
+        let template = frame.template;
+        let source = template.source();
+        for node in nodes {
+            if self.staging && self.stage(out, frame, node, context)? {
+                continue;
+            }
+            match node {
+                Node::Text(text) => out.write_str(&source[text.clone()])?,
+                Node::Placeholder { path, filters, required, default, span } => {
+                    let tag = &source[span.clone()];
+                    let value = match lookup(context, &source[path.clone()], tag) {
+                        // The fallback text is template text, so it is neither filtered nor escaped.
+                        Ok(Value::Null) | Err(_) if let Some(default) = default => {
+                            out.write_str(&source[default.clone()])?;
+                            continue;
+                        }
+                        Ok(Value::Null) | Err(IssueKind::Unresolved { .. }) if *required => {
+                            Err(IssueKind::MissingRequired { tag: tag.to_string() })
+                        }
+                        // A `default` filter stands in for a missing value.
+                        Err(_) if filters.iter().any(|filter| &source[filter.name.clone()] == "default") => Ok(Value::Null),
+                        value => value,
+                    };
+                    match value.and_then(|value| self.apply_filters(value, source, filters, tag)) {
+                        // Every line after the first starts at the column of the placeholder.
+                        Ok(value) if template.indent() => {
+                            let indentation = out.indentation();
+                            value.write_to(&mut Indent::new(out, &indentation), template.escape())?;
+                        }
+                        Ok(value) => value.write_to(out, template.escape())?,
+                        // A later stage reports the tag again, as `resolve` drops the issues.
+                        Err(kind) if self.staging => {
+                            out.write_tag(&bare_tag(source, span.clone(), template.delimiters()))?;
+                            self.report(frame, kind, span.clone());
+                        }
+                        Err(kind) => {
+                            out.write_str(tag)?;
+                            self.report(frame, kind, span.clone());
+                        }
+                    }
+                }
+                Node::Section { negated, path, then, otherwise } => {
+                    let path = &source[path.clone()];
+                    let truthy = lookup(context, path, path).is_ok_and(|value| value.is_truthy());
+                    let branch = if truthy != *negated { then } else { otherwise };
+                    self.write_nodes(out, frame, branch, context)?;
+                }
+                Node::Each { path, alias, span, body, otherwise } => {
+                    let items = match lookup(context, &source[path.clone()], &source[span.clone()]) {
+                        Ok(Value::List(items)) => items,
+                        Ok(value) if !value.is_truthy() => Vec::new(),
+                        Ok(value) => vec![value],
+                        Err(kind) => {
+                            self.report(frame, kind, span.clone());
+                            Vec::new()
+                        }
+                    };
+                    if items.is_empty() {
+                        self.write_nodes(out, frame, otherwise, context)?;
+                    }
+                    let count = items.len();
+                    for (index, item) in items.into_iter().enumerate() {
+                        let scope = Scope {
+                            parent: context,
+                            bindings: vec![
+                                (&source[alias.clone()], item),
+                                ("@index", Value::Int(index as i128)),
+                                ("@first", Value::Bool(index == 0)),
+                                ("@last", Value::Bool(index + 1 == count)),
+                            ],
+                        };
+                        self.write_nodes(out, frame, body, &scope)?;
+                    }
+                }
+                Node::Partial { name, args, span } => {
+                    let tag = &source[span.clone()];
+                    let name = &source[name.clone()];
+                    let Some((registered, partial)) = self.registry.and_then(|registry| registry.entry(name)) else {
+                        out.write_str(tag)?;
+                        self.report(frame, IssueKind::UnknownPartial { tag: tag.to_string(), name: name.to_string() }, span.clone());
+                        continue;
+                    };
+                    if self.stack.contains(&registered) {
+                        out.write_str(tag)?;
+                        self.report(frame, IssueKind::PartialCycle { tag: tag.to_string(), name: name.to_string() }, span.clone());
+                        continue;
+                    }
+                    let limit = self.registry.map_or(0, TemplateRegistry::max_depth);
+                    if self.depth >= limit {
+                        out.write_str(tag)?;
+                        self.report(frame, IssueKind::DepthLimit { tag: tag.to_string(), limit }, span.clone());
+                        continue;
+                    }
+
+                    let mut bindings = Vec::with_capacity(args.len());
+                    for arg in args {
+                        let value = &source[arg.value.clone()];
+                        if arg.literal {
+                            // Literals are template text, so they are not escaped.
+                            bindings.push((&source[arg.key.clone()], Value::Raw(Cow::Borrowed(value))));
+                            continue;
+                        }
+                        match lookup(context, value, tag) {
+                            Ok(value) => bindings.push((&source[arg.key.clone()], value)),
+                            Err(kind) => self.report(frame, kind, span.clone()),
+                        }
+                    }
+
+                    self.depth += 1;
+                    let result = self.render_into(out, partial, Some(registered), &Scope { parent: context, bindings });
+                    self.depth -= 1;
+                    result?;
+                }
+                // Reached only when the layout could not be resolved.
+                Node::Extends { span, .. } => out.write_str(&source[span.clone()])?,
+                Node::Block { name, .. } => {
+                    let name = &source[name.clone()];
+                    if let Some((level, definition, body)) = self.find_block(name, 0) {
+                        self.write_block(out, name, level, definition, body, context)?;
+                    }
+                }
+                Node::Super => {
+                    if let Some(&(name, level)) = self.blocks.last()
+                        && let Some((level, definition, body)) = self.find_block(name, level + 1)
+                    {
+                        self.write_block(out, name, level, definition, body, context)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'r> Renderer<'r> {
+    /// When staging, writes `node` back as tags if `context` does not supply its value, with
+    /// its content staged, and returns whether it did. Partials, layouts and blocks are always
+    /// written back, as they are resolved through a registry in the last stage.
+    fn stage<W>(&mut self, out: &mut Output<'_, W>, frame: Frame<'r>, node: &'r Node, context: &dyn Context) -> Result<bool, fmt::Error>
+    where
+        W: fmt::Write + ?Sized,
+    {
+        let template = frame.template;
+        let source = template.source();
+        let (open, close) = (template.delimiters().open(), template.delimiters().close());
+        match node {
+            Node::Placeholder { path, span, .. } if !supplied(context, &source[path.clone()]) => {
+                out.write_tag(&bare_tag(source, span.clone(), template.delimiters()))?;
+            }
+            Node::Partial { span, .. } | Node::Extends { span, .. } => {
+                out.write_tag(&bare_tag(source, span.clone(), template.delimiters()))?;
+            }
+            Node::Section { negated, path, then, otherwise } if !supplied(context, &source[path.clone()]) => {
+                let keyword = if *negated { "unless" } else { "if" };
+                out.write_tag(&format!("{open}#{keyword} {}{close}", &source[path.clone()]))?;
+                self.write_nodes(out, frame, then, context)?;
+                if !otherwise.is_empty() {
+                    out.write_tag(&format!("{open}else{close}"))?;
+                    self.write_nodes(out, frame, otherwise, context)?;
+                }
+                out.write_tag(&format!("{open}/{keyword}{close}"))?;
+            }
+            Node::Each { path, alias, body, otherwise, .. } if !supplied(context, &source[path.clone()]) => {
+                let alias = &source[alias.clone()];
+                out.write_tag(&format!("{open}#each {} as {alias}{close}", &source[path.clone()]))?;
+                // The item and the loop variables are bound in the later stage too.
+                let scope = Unbound { parent: context, names: [alias, "@index", "@first", "@last"] };
+                self.write_nodes(out, frame, body, &scope)?;
+                if !otherwise.is_empty() {
+                    out.write_tag(&format!("{open}else{close}"))?;
+                    self.write_nodes(out, frame, otherwise, context)?;
+                }
+                out.write_tag(&format!("{open}/each{close}"))?;
+            }
+            Node::Block { name, body } => {
+                out.write_tag(&format!("{open}#block {}{close}", &source[name.clone()]))?;
+                self.write_nodes(out, frame, body, context)?;
+                out.write_tag(&format!("{open}/block{close}"))?;
+            }
+            Node::Super => out.write_tag(&format!("{open}super(){close}"))?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Applies `filters` to `value` in order, preferring the filters registered on the registry
+    /// over the built-in ones.
+    fn apply_filters<'v>(
+        &self,
+        mut value: Value<'v>,
+        source: &str,
+        filters: &[FilterCall],
+        tag: &str,
+    ) -> Result<Value<'v>, IssueKind> {
+        for call in filters {
+            let name = &source[call.name.clone()];
+            let args: Vec<&str> = call.args.iter().map(|arg| &source[arg.clone()]).collect();
+            let result = match self.registry.and_then(|registry| registry.filter(name)) {
+                Some(custom) => custom.apply(value, &args),
+                None => filter::builtin(name, value, &args)
+                    .ok_or_else(|| IssueKind::UnknownFilter { tag: tag.to_string(), name: name.to_string() })?,
+            };
+            value = result.map_err(|message| IssueKind::FilterFailed {
+                tag: tag.to_string(),
+                filter: name.to_string(),
+                message,
+            })?;
+        }
+        Ok(value)
+    }
+
+    /// Finds the most derived definition of the block `name`, starting at `layouts[from]`.
+    fn find_block(&self, name: &str, from: usize) -> Option<(usize, Frame<'r>, &'r [Node])> {
+        self.layouts.iter().enumerate().skip(from).find_map(|(level, frame)| {
+            let source = frame.template.source();
+            find_block(frame.template.nodes(), source, name).map(|body| (level, *frame, body))
+        })
+    }
+
+    fn write_block<W>(
+        &mut self,
+        out: &mut Output<'_, W>,
+        name: &'r str,
+        level: usize,
+        definition: Frame<'r>,
+        body: &'r [Node],
+        context: &dyn Context,
+    ) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+    {
+        self.blocks.push((name, level));
+        let result = self.write_nodes(out, definition, body, context);
+        self.blocks.pop();
+        result
+    }
+}
+
+/// Finds the body of the block `name` anywhere in `nodes`.
+fn find_block<'n>(nodes: &'n [Node], source: &str, name: &str) -> Option<&'n [Node]> {
+    nodes.iter().find_map(|node| match node {
+        Node::Block { name: block, body } if &source[block.clone()] == name => Some(body.as_slice()),
+        Node::Block { body, .. } => find_block(body, source, name),
+        Node::Section { then, otherwise, .. } | Node::Each { body: then, otherwise, .. } => {
+            find_block(then, source, name).or_else(|| find_block(otherwise, source, name))
+        }
+        _ => None,
+    })
+}
+
+/// A context that binds a few names over an enclosing context: the item and loop variables
+/// inside an `{{#each}}` loop, or the arguments of a partial.
+struct Scope<'a> {
+    parent: &'a dyn Context,
+    bindings: Vec<(&'a str, Value<'a>)>,
+}
+
+impl Context for Scope<'_> {
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        for (name, value) in &self.bindings {
+            if key == *name {
+                return Some(value.to_value());
+            }
+            // Paths into a bound value are walked from the binding, never looked up in the parent.
+            if key.strip_prefix(name).is_some_and(|rest| rest.starts_with('.')) {
+                return None;
+            }
+        }
+        self.parent.get(key)
+    }
+}
+
+/// A context that hides the item and loop variables of an `{{#each}}` loop left for a later
+/// stage, so that they are not taken from an enclosing context.
+struct Unbound<'a> {
+    parent: &'a dyn Context,
+    names: [&'a str; 4],
+}
+
+impl Context for Unbound<'_> {
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        let bound = |name: &&str| key.strip_prefix(name).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+        if self.names.iter().any(bound) { None } else { self.parent.get(key) }
+    }
+}
+
+/// Returns `true` if `context` has a value for `path` or for its first segment.
+fn supplied(context: &dyn Context, path: &str) -> bool {
+    let root = path.split('.').next().unwrap_or_default();
+    context.get(path).is_some() || context.get(root).is_some()
+}
+
+/// Returns the text of the tag at `span` without its trim markers, which have already been
+/// applied to the text around it.
+fn bare_tag<'s>(source: &'s str, span: Range<usize>, delimiters: &Delimiters) -> Cow<'s, str> {
+    let tag = &source[span.clone()];
+    let Some(parsed) = parser::next_tag(source.as_bytes(), span.start, delimiters.delims()) else {
+        return Cow::Borrowed(tag);
+    };
+    if parsed.start != span.start || !(parsed.trim_before || parsed.trim_after) {
+        return Cow::Borrowed(tag);
+    }
+    let (open, close) = (delimiters.open(), delimiters.close());
+    let mut inner = &tag[open.len()..tag.len() - close.len()];
+    if parsed.trim_before {
+        inner = &inner[1..];
+    }
+    if parsed.trim_after {
+        inner = inner.trim_end().strip_suffix('-').unwrap_or(inner);
+    }
+    Cow::Owned(format!("{open}{}{close}", inner.trim()))
+}
+
+/// Resolves a dotted `path` against `context`, trying the whole path as a key first. `tag` is
+/// the tag text used in the error.
+pub(crate) fn lookup<'c>(context: &'c dyn Context, path: &str, tag: &str) -> Result<Value<'c>, IssueKind> {
+    if let Some(value) = context.get(path) {
+        return Ok(value);
+    }
+
+    let mut segments = path.split('.');
+    let root = segments.next().unwrap_or_default();
+    let mut value = context.get(root).ok_or_else(|| IssueKind::Unresolved { tag: tag.to_string() })?;
+    let mut resolved = root.len();
+    for segment in segments {
+        value = value.field(segment).ok_or_else(|| IssueKind::MissingSegment {
+            tag: tag.to_string(),
+            path: path[..resolved].to_string(),
+            segment: segment.to_string(),
+        })?;
+        resolved += 1 + segment.len();
+    }
+    Ok(value)
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::io;
+
+use super::delimiters::Delimiters;
+
+/// Writes formatted text straight into an `io::Write` sink and keeps the first I/O error, which
+/// `fmt::Error` cannot carry.
+pub(crate) struct IoSink<'w, W: ?Sized> {
+    inner: &'w mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write + ?Sized> fmt::Write for IoSink<'_, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.inner.write_all(text.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+/// Runs `write` over `out` and turns a failure back into the I/O error that caused it.
+pub(crate) fn write_io<W, F>(out: &mut W, write: F) -> io::Result<()>
+where
+    W: io::Write + ?Sized,
+    F: FnOnce(&mut IoSink<'_, W>) -> fmt::Result,
+{
+    let mut sink = IoSink { inner: out, error: None };
+    match write(&mut sink) {
+        Ok(()) => Ok(()),
+        Err(fmt::Error) => Err(sink.error.take().unwrap_or_else(|| io::Error::other("formatting a value failed"))),
+    }
+}
+
+/// The output of a render: forwards everything to `inner` and keeps track of the current line,
+/// so that multi-line values can be indented to the column their placeholder starts at.
+///
+/// An output created with `literal` writes a template source instead: every opening delimiter
+/// it is given is escaped as a `{{"{{"}}` literal tag, so that the text reads back as literal
+/// text. Tags are written as they are with `write_tag`.
+pub(crate) struct Output<'o, W: ?Sized> {
+    inner: &'o mut W,
+    /// The whitespace at the start of the current line.
+    indent: String,
+    /// The number of characters written to the current line after `indent`.
+    column: usize,
+    /// The opening delimiter to escape and the literal tag that escapes it, when writing a
+    /// template source.
+    literal: Option<(String, String)>,
+    /// The end of the text written last when it could be the start of an opening delimiter,
+    /// held back until the next write tells whether it must be escaped.
+    held: String,
+}
+
+impl<'o, W: fmt::Write + ?Sized> Output<'o, W> {
+    pub(crate) fn new(inner: &'o mut W) -> Self {
+        Output { inner, indent: String::new(), column: 0, literal: None, held: String::new() }
+    }
+
+    /// Creates an output that writes a template source with the tag delimiters `delimiters`.
+    pub(crate) fn literal(inner: &'o mut W, delimiters: &Delimiters) -> Self {
+        let (open, close) = (delimiters.open(), delimiters.close());
+        Output { literal: Some((open.to_string(), format!("{open}\"{open}\"{close}"))), ..Output::new(inner) }
+    }
+
+    /// Writes a tag of a template source unescaped.
+    pub(crate) fn write_tag(&mut self, tag: &str) -> fmt::Result {
+        self.flush()?;
+        self.track(tag);
+        self.inner.write_str(tag)
+    }
+
+    /// Writes the text held back by a literal output.
+    pub(crate) fn flush(&mut self) -> fmt::Result {
+        let held = std::mem::take(&mut self.held);
+        self.inner.write_str(&held)
+    }
+
+    /// Returns the indentation that lines up with the current column: the leading whitespace of
+    /// the current line followed by a space for every other character on it.
+    pub(crate) fn indentation(&self) -> String {
+        let mut indentation = self.indent.clone();
+        indentation.extend(std::iter::repeat_n(' ', self.column));
+        indentation
+    }
+}
+
+impl<W: fmt::Write + ?Sized> Output<'_, W> {
+    /// Updates the current line with `text`.
+    fn track(&mut self, text: &str) {
+        let line = match text.rfind('\n') {
+            Some(newline) => {
+                self.indent.clear();
+                self.column = 0;
+                &text[newline + 1..]
+            }
+            None => text,
+        };
+        for c in line.chars() {
+            if self.column == 0 && (c == ' ' || c == '\t') {
+                self.indent.push(c);
+            } else {
+                self.column += 1;
+            }
+        }
+    }
+}
+
+impl<W: fmt::Write + ?Sized> fmt::Write for Output<'_, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.track(text);
+        let Some((open, escaped)) = &self.literal else {
+            return self.inner.write_str(text);
+        };
+        if text.is_empty() {
+            return Ok(());
+        }
+        let mut text = text;
+        let joined;
+        if !self.held.is_empty() {
+            joined = std::mem::take(&mut self.held) + text;
+            text = &joined;
+        }
+
+        let mut written = 0;
+        while let Some(found) = text[written..].find(open.as_str()) {
+            let at = written + found;
+            self.inner.write_str(&text[written..at])?;
+            self.inner.write_str(escaped)?;
+            written = at + open.len();
+        }
+        // Hold back an end that an opening delimiter written next could complete.
+        let rest = &text[written..];
+        let hold = (1..open.len())
+            .rev()
+            .find(|&length| open.is_char_boundary(length) && rest.ends_with(&open[..length]))
+            .unwrap_or(0);
+        self.inner.write_str(&rest[..rest.len() - hold])?;
+        self.held.push_str(&rest[rest.len() - hold..]);
+        Ok(())
+    }
+}
+
+/// Writes text with every line after the first prefixed by `indentation`, except empty lines.
+pub(crate) struct Indent<'o, W: ?Sized> {
+    inner: &'o mut W,
+    indentation: &'o str,
+    /// Set after a line break, until the next line turns out not to be empty.
+    pending: bool,
+}
+
+impl<'o, W: fmt::Write + ?Sized> Indent<'o, W> {
+    pub(crate) fn new(inner: &'o mut W, indentation: &'o str) -> Self {
+        Indent { inner, indentation, pending: false }
+    }
+}
+
+impl<W: fmt::Write + ?Sized> fmt::Write for Indent<'_, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                self.inner.write_str("\n")?;
+                self.pending = true;
+            }
+            if line.is_empty() || line == "\r" {
+                self.inner.write_str(line)?;
+                continue;
+            }
+            if self.pending {
+                self.inner.write_str(self.indentation)?;
+                self.pending = false;
+            }
+            self.inner.write_str(line)?;
+        }
+        Ok(())
+    }
+}
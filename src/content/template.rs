@@ -0,0 +1,397 @@
+use std::fmt;
+use std::io;
+
+use super::context::Context;
+use super::error::{Issue, RenderError};
+use super::delimiters::Delimiters;
+use super::escape::Escape;
+use super::parser::{self, Node};
+use super::placeholder::{self, Placeholder};
+use super::render::Renderer;
+use super::sink;
+
+/// ### Template
+///
+/// Pre-Parsed Template
+///
+/// A `Template` parses its source once into static text segments and placeholder slots, so it
+/// can be rendered any number of times without scanning the source again. Values come from a
+/// `Context` and are escaped with the template's `Escape` mode (`Escape::None` by default).
+/// Placeholders without a value are left in the output unchanged; `render_strict` reports them
+/// as a `RenderError` instead.
+///
+/// `{{#if path}}...{{else}}...{{/if}}` renders its first part when the value at `path` is
+/// truthy and the part after the optional `{{else}}` otherwise; `{{#unless path}}` does the
+/// opposite. Empty text, `false`, zero, `None` and empty collections are falsy, as is a
+/// missing value. Sections are parsed from the template source only, so values that contain
+/// section tags are written as plain text and can never open a section. In strict mode, an
+/// `{{#each}}` over a missing value is reported, while an `{{#if}}` over one is simply false.
+///
+/// A placeholder can be a dotted path such as `{{user.address.city}}` or `{{items.0.name}}`.
+/// The context is first asked for the whole path, so flat keys containing dots keep working;
+/// otherwise the first segment is looked up in the context and each following segment walks
+/// into a nested context by key or into a list by index.
+///
+/// `do_replace!`, `do_html!`, `do_xml!` and `do_json!` are built on `Template`; use it directly
+/// when the same template is rendered many times.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{Escape, Template};
+///
+/// pub const HOME_TEMPLATE: &str = "<h1>{{title}}</h1><p>{{body}}</p>";
+///
+/// let home = Template::new(HOME_TEMPLATE).with_escape(Escape::Html);
+///
+/// let page = home.render(&[("title", "Home"), ("body", "Fish & Chips")]);
+/// assert_eq!(page, "<h1>Home</h1><p>Fish &amp; Chips</p>");
+///
+/// let mut output = String::new();
+/// home.render_to(&mut output, &[("title", "About")]).unwrap();
+/// assert_eq!(output, "<h1>About</h1><p>{{body}}</p>");
+/// ```
+///
+/// #### Example: Conditional sections
+/// ```rust
+/// use cans::do_html;
+///
+/// const CART_TEMPLATE: &str = "{{#if items}}{{count}} items{{else}}Your cart is empty{{/if}}\
+///     {{#unless signed_in}}, <a href=\"/login\">sign in</a>{{/unless}}";
+///
+/// let empty: Vec<&str> = Vec::new();
+/// assert_eq!(
+///     do_html!(CART_TEMPLATE, items = empty, count = 0, signed_in = false),
+///     "Your cart is empty, <a href=\"/login\">sign in</a>"
+/// );
+/// assert_eq!(do_html!(CART_TEMPLATE, items = ["tea"], count = 1, signed_in = true), "1 items");
+///
+/// // Section tags inside values are plain text.
+/// assert_eq!(
+///     do_html!("{{#if name}}Hi {{name}}{{/if}}", name = "{{/if}}"),
+///     "Hi {{/if}}"
+/// );
+/// ```
+///
+/// #### Example: Loops
+///
+/// `{{#each rows as row}}...{{/each}}` renders its body once per item of a list, with the item
+/// bound to `row` and the loop variables `@index`, `@first` and `@last` available. An optional
+/// `{{else}}` part is rendered when there are no items.
+/// ```rust
+/// use cans::do_html;
+///
+/// #[derive(cans::Context)]
+/// struct Row {
+///     name: &'static str,
+///     price: u32,
+/// }
+///
+/// const TABLE_TEMPLATE: &str = "<table>{{#each rows as row}}\
+///     <tr class=\"{{#if @first}}first{{/if}}\"><td>{{@index}}</td><td>{{row.name}}</td><td>{{row.price}}</td></tr>\
+///     {{else}}<tr><td>No rows</td></tr>{{/each}}</table>";
+///
+/// let rows = vec![Row { name: "Tea & Milk", price: 3 }, Row { name: "Cake", price: 5 }];
+/// assert_eq!(
+///     do_html!(TABLE_TEMPLATE, rows = rows),
+///     "<table><tr class=\"first\"><td>0</td><td>Tea &amp; Milk</td><td>3</td></tr>\
+///      <tr class=\"\"><td>1</td><td>Cake</td><td>5</td></tr></table>"
+/// );
+/// assert_eq!(do_html!(TABLE_TEMPLATE, rows = Vec::<Row>::new()), "<table><tr><td>No rows</td></tr></table>");
+/// ```
+///
+/// #### Example: Dotted paths into nested data
+/// ```rust
+/// use cans::content::Template;
+///
+/// #[derive(cans::Context)]
+/// struct Address {
+///     city: String,
+/// }
+///
+/// #[derive(cans::Context)]
+/// struct User {
+///     name: String,
+///     address: Address,
+///     tags: Vec<&'static str>,
+/// }
+///
+/// let user = User {
+///     name: "Ahmed".into(),
+///     address: Address { city: "Khartoum".into() },
+///     tags: vec!["admin", "author"],
+/// };
+///
+/// let template = Template::new("{{user.name}} ({{user.tags.0}}) from {{user.address.city}}");
+/// assert_eq!(template.render(&[("user", &user)]), "Ahmed (admin) from Khartoum");
+///
+/// let error = Template::new("{{user.address.zip}}").render_strict(&[("user", &user)]).unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     "template could not be rendered:\n- `{{user.address.zip}}` (line 1, column 1, bytes 0..20): `user.address` has no `zip`"
+/// );
+/// ```
+///
+/// #### Example: Filters
+///
+/// A placeholder can pass its value through filters, applied from left to right:
+/// `{{name|upper}}`, `{{price|round:2}}` or `{{tags|join:", "|upper}}`. Arguments follow a `:`
+/// and are separated by `,`; an argument is a bare word such as `2` or a `"quoted"` string.
+/// The result is escaped with the template's `Escape` mode like any other value. The built-in
+/// filters are:
+/// - `upper`, `lower`, `capitalize` (first letter) and `title` (first letter of every word);
+/// - `trim`;
+/// - `truncate:length` or `truncate:length,"suffix"`, which cuts the text to `length`
+///   characters and appends the suffix (`...` by default) when anything was cut;
+/// - `default:"text"`, which replaces a falsy or missing value with `text`, written unescaped;
+/// - `join` or `join:"separator"`, which joins the items of a list (with `, ` by default);
+/// - `round` or `round:places`, which formats a number with a fixed number of decimal places;
+/// - `number:places,"thousands"` or `number:places,"thousands","decimal point"`, which also
+///   groups the digits, as in `{{total|number:2,","}}` for `1,234.50`; an empty `places`, as in
+///   `number:"",","`, keeps as many decimal places as needed;
+/// - `raw`, which writes the value without escaping;
+/// - `escape_html`, `escape_xml` and `escape_json`, which escape the value for another format
+///   and write the result unescaped;
+/// - `urlencode`, which percent-encodes the value for use in a URL.
+///
+/// Text filters such as `upper` apply to each item of a list. Custom filters can be registered
+/// on a `TemplateRegistry`. An unknown filter, or one that cannot be applied, such as `round`
+/// on text that is not a number, leaves the tag unchanged and is reported by `render_strict`.
+/// ```rust
+/// use cans::do_html;
+///
+/// const PRODUCT_TEMPLATE: &str = "<h2>{{name|title}}</h2><p>{{bio|truncate:12}}</p>\
+///     <b>{{price|round:2}}</b> <a href=\"/search?q={{name|urlencode}}\">{{tags|upper|join:\" / \"}}</a>\
+///     {{note|default:\"<i>none</i>\"}}{{badge|raw}}";
+///
+/// let page = do_html!(
+///     PRODUCT_TEMPLATE,
+///     name = "fish & chips",
+///     bio = "Fresh every single day",
+///     price = 4.5,
+///     tags = ["hot", "salty"],
+///     badge = "<span>new</span>",
+/// );
+/// assert_eq!(
+///     page,
+///     "<h2>Fish &amp; Chips</h2><p>Fresh every ...</p><b>4.50</b> \
+///      <a href=\"/search?q=fish%20%26%20chips\">HOT / SALTY</a><i>none</i><span>new</span>"
+/// );
+/// ```
+///
+/// #### Example: Fallbacks and required values
+///
+/// `{{subtitle ? "Welcome"}}` writes `Welcome` when the context has no value for `subtitle` or
+/// its value is null, such as `None`. The fallback is template text, so it is written without
+/// escaping or filters. `{{title!}}` marks a value that must always be supplied: when it is
+/// missing or null, the tag is left unchanged and `render_strict` reports it, which lets tests
+/// catch forgotten values. The content macros do not require an argument for a placeholder
+/// with a fallback.
+/// ```rust
+/// use cans::content::{IssueKind, Template};
+/// use cans::do_html;
+///
+/// const HERO_TEMPLATE: &str = "<h1>{{title!}}</h1><p>{{subtitle ? \"Welcome\"}}</p>";
+///
+/// assert_eq!(do_html!(HERO_TEMPLATE, title = "Home"), "<h1>Home</h1><p>Welcome</p>");
+/// assert_eq!(do_html!(HERO_TEMPLATE, title = "Home", subtitle = Some("Hi")), "<h1>Home</h1><p>Hi</p>");
+///
+/// let error = Template::new(HERO_TEMPLATE).render_strict(&[("subtitle", "Hi")]).unwrap_err();
+/// assert_eq!(error.issues()[0].kind, IssueKind::MissingRequired { tag: "{{title!}}".into() });
+/// ```
+///
+/// #### Example: Comments and whitespace control
+///
+/// `{{! note }}` is a comment: it runs to the first `}}` and is never written. A `{{- ` at the
+/// start of any tag removes the whitespace, line breaks included, before the tag, and a ` -}}`
+/// at its end removes the whitespace after it. The space next to the `-` is required, so keys
+/// such as `{{-x}}` or `{{x-}}` keep working, and a tag that starts with `{{- ` may also end
+/// with ` }}`.
+/// ```rust
+/// use cans::content::Template;
+///
+/// let list = Template::new(
+///     "{{! one item per line, no blank lines }}<ul>\n\
+///      {{- #each items as item }}\n  <li>{{item}}</li>\n{{- /each }}\n</ul>",
+/// );
+/// assert_eq!(list.render(&[("items", vec!["a", "b"])]), "<ul>\n  <li>a</li>\n  <li>b</li>\n</ul>");
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    nodes: Vec<Node>,
+    /// Misplaced or unclosed section tags found while parsing.
+    issues: Vec<Issue>,
+    escape: Escape,
+    delimiters: Delimiters,
+    indent: bool,
+}
+
+impl Template {
+    /// Parses `source` into a template that inserts values unchanged.
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let delimiters = Delimiters::default();
+        let (nodes, issues) = parser::parse(&source, delimiters.delims());
+
+        Template { source, nodes, issues, escape: Escape::None, delimiters, indent: false }
+    }
+
+    /// Parses the template again with other tag delimiters, such as `[[` and `]]`. See
+    /// `Delimiters`.
+    pub fn with_delimiters(mut self, delimiters: Delimiters) -> Self {
+        if delimiters != self.delimiters {
+            (self.nodes, self.issues) = parser::parse(&self.source, delimiters.delims());
+            self.delimiters = delimiters;
+        }
+        self
+    }
+
+    /// Returns the tag delimiters.
+    pub fn delimiters(&self) -> &Delimiters {
+        &self.delimiters
+    }
+
+    /// Sets how values are escaped when the template is rendered.
+    pub fn with_escape(mut self, escape: Escape) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Returns the escaping mode used for values.
+    pub fn escape(&self) -> Escape {
+        self.escape
+    }
+
+    /// Sets whether every line of a multi-line value after the first is indented to the column
+    /// of its placeholder, for generating YAML, Python or other indentation-sensitive source.
+    /// Empty lines are left empty.
+    pub fn with_indent(mut self, indent: bool) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Returns `true` if multi-line values are indented to the column of their placeholder.
+    pub fn indent(&self) -> bool {
+        self.indent
+    }
+
+    /// Returns the template source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Renders the template with the values from `context` into a new `String`.
+    pub fn render<C: Context + ?Sized>(&self, context: &C) -> String {
+        let mut output = String::with_capacity(self.source.len());
+        self.render_to(&mut output, context)
+            .expect("writing to a String does not fail");
+        output
+    }
+
+    /// Renders the template with the values from `context` into `out`, writing each piece as
+    /// soon as it is produced.
+    pub fn render_to<W, C>(&self, out: &mut W, context: &C) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        C: Context + ?Sized,
+    {
+        Renderer::new(None).render(out, self, None, &context)
+    }
+
+    /// Renders the template with the values from `context` straight into an `io::Write` sink
+    /// such as a file, a socket or a chunked response body, without building the output in
+    /// memory first. Wrap unbuffered sinks in an `io::BufWriter`.
+    ///
+    /// ```rust
+    /// use cans::content::{Escape, Template};
+    ///
+    /// let template = Template::new("<li>{{name}}</li>").with_escape(Escape::Html);
+    /// let mut body: Vec<u8> = Vec::new();
+    /// template.write_to(&mut body, &[("name", "Fish & Chips")]).unwrap();
+    /// assert_eq!(body, b"<li>Fish &amp; Chips</li>");
+    /// ```
+    pub fn write_to<W, C>(&self, out: &mut W, context: &C) -> io::Result<()>
+    where
+        W: io::Write + ?Sized,
+        C: Context + ?Sized,
+    {
+        sink::write_io(out, |out| self.render_to(out, context))
+    }
+
+    /// Resolves the values that `context` supplies and returns the rest of the template as a new
+    /// template, for rendering in stages: a site-wide stage can fill in the brand and the
+    /// navigation once, and each request then renders the result with its own values. Tags
+    /// whose value is not supplied, partials, layouts and blocks are kept for a later stage,
+    /// while the supplied values are written as literal text, escaped with the template's
+    /// `Escape` mode, so that a later stage never reads a tag in them. The new template keeps
+    /// the escaping mode, delimiters and indentation mode, and rendering it produces the same
+    /// text as rendering this template with both stages' values at once. A value counts as
+    /// supplied when the context has its first path segment, so `{{user.name}}` is resolved as
+    /// soon as the context has `user`. Tags that cannot be resolved with a supplied value, such
+    /// as a path into it that does not exist, are also kept, so that `render_strict` reports
+    /// them in the last stage.
+    ///
+    /// ```rust
+    /// use cans::content::{Escape, Template};
+    ///
+    /// let page = Template::new("<title>{{brand}}</title>{{#if admin}}<nav>{{menu}}</nav>{{/if}}<p>Hi {{user}}</p>")
+    ///     .with_escape(Escape::Html);
+    ///
+    /// // The brand contains a tag-like text, which stays literal in later stages.
+    /// let site = page.resolve(&[("brand", "{{user}} & Co")]);
+    /// assert_eq!(site.source(), "<title>{{\"{{\"}}user}} &amp; Co</title>{{#if admin}}<nav>{{menu}}</nav>{{/if}}<p>Hi {{user}}</p>");
+    ///
+    /// let html = site.render(&[("user", "<Amal>")]);
+    /// assert_eq!(html, "<title>{{user}} &amp; Co</title><p>Hi &lt;Amal&gt;</p>");
+    /// ```
+    pub fn resolve<C: Context + ?Sized>(&self, context: &C) -> Template {
+        let mut source = String::with_capacity(self.source.len());
+        Renderer::new(None)
+            .staging()
+            .render(&mut source, self, None, &context)
+            .expect("writing to a String does not fail");
+        Template::new(source)
+            .with_escape(self.escape)
+            .with_delimiters(self.delimiters.clone())
+            .with_indent(self.indent)
+    }
+
+    /// Lists the values, sections, loops, partials, layouts and blocks the template refers to.
+    /// See `placeholders`.
+    pub fn placeholders(&self) -> Vec<Placeholder> {
+        placeholder::scan(&self.source, self.delimiters.delims())
+    }
+
+    /// Renders the template like `render`, but returns a `RenderError` listing every placeholder
+    /// that could not be resolved, every malformed tag and every misplaced or unclosed section
+    /// tag, with its line and column, instead of leaving them in the output.
+    pub fn render_strict<C: Context + ?Sized>(&self, context: &C) -> Result<String, RenderError> {
+        let mut output = String::with_capacity(self.source.len());
+        let mut renderer = Renderer::new(None);
+        renderer
+            .render(&mut output, self, None, &context)
+            .expect("writing to a String does not fail");
+        renderer.finish().map(|()| output)
+    }
+
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub(crate) fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+}
+
+impl From<&str> for Template {
+    fn from(source: &str) -> Self {
+        Template::new(source)
+    }
+}
+
+impl From<String> for Template {
+    fn from(source: String) -> Self {
+        Template::new(source)
+    }
+}
@@ -0,0 +1,573 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+use super::context::Context;
+use super::escape::{Escape, Raw};
+use super::filter;
+
+/// ### Value
+///
+/// Placeholder Value
+///
+/// A `Value` is what a `Context` returns for a placeholder. `Text` is escaped with the
+/// template's `Escape` mode when it is written, while `Raw` is always written unchanged. `Json`
+/// holds JSON text, such as a `JsonArray`: `Escape::Json` writes it unchanged, so that it stays
+/// valid JSON in a `do_json!` template, and every other mode escapes it like `Text`.
+/// `Bool`, `Int` and `Float` keep booleans and numbers typed, and `Null` stands for a missing
+/// optional value, such as `None`, and writes nothing. `Context` holds a nested context, such as a struct field whose type derives `Context`;
+/// it writes nothing by itself. `List` holds the items of a sequence and is written as its
+/// items separated by `, `. Dotted placeholders such as `{{user.address.city}}` and
+/// `{{items.0.name}}` walk into `Context` values by key and into `List` values by index.
+/// Formatting a value with `Display` writes it without escaping.
+///
+/// `{{#if}}` and `{{#unless}}` sections test whether a value is truthy: empty text, `false`,
+/// zero, `Null` and empty lists are falsy, everything else is truthy.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{Escape, Value};
+///
+/// let mut output = String::new();
+/// Value::from("<b>").write_to(&mut output, Escape::Html).unwrap();
+/// Value::Raw("<br>".into()).write_to(&mut output, Escape::Html).unwrap();
+/// assert_eq!(output, "&lt;b&gt;<br>");
+/// ```
+/// <small>End Doc</small>
+#[derive(Clone)]
+pub enum Value<'a> {
+    /// Text that is escaped for the output format.
+    Text(Cow<'a, str>),
+    /// Trusted text that is written without escaping.
+    Raw(Cow<'a, str>),
+    /// JSON text, written without escaping into JSON and escaped like `Text` for other formats.
+    Json(Cow<'a, str>),
+    /// A boolean, written as `true` or `false`.
+    Bool(bool),
+    /// An integer.
+    Int(i128),
+    /// A floating-point number.
+    Float(f64),
+    /// No value, written as nothing.
+    Null,
+    /// A nested context.
+    Context(&'a dyn Context),
+    /// The items of a sequence.
+    List(Vec<Value<'a>>),
+}
+
+impl fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(text) => f.debug_tuple("Text").field(text).finish(),
+            Value::Raw(text) => f.debug_tuple("Raw").field(text).finish(),
+            Value::Json(text) => f.debug_tuple("Json").field(text).finish(),
+            Value::Bool(value) => f.debug_tuple("Bool").field(value).finish(),
+            Value::Int(value) => f.debug_tuple("Int").field(value).finish(),
+            Value::Float(value) => f.debug_tuple("Float").field(value).finish(),
+            Value::Null => f.write_str("Null"),
+            Value::Context(_) => f.write_str("Context(..)"),
+            Value::List(items) => f.debug_tuple("List").field(items).finish(),
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    /// Writes the value without escaping, as `write_to` does with `Escape::None`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to(f, Escape::None)
+    }
+}
+
+impl PartialEq for Value<'_> {
+    /// Text values compare by content; nested contexts compare by address.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Text(a), Value::Text(b)) | (Value::Raw(a), Value::Raw(b)) | (Value::Json(a), Value::Json(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            (Value::Context(a), Value::Context(b)) => std::ptr::addr_eq(*a, *b),
+            (Value::List(a), Value::List(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Writes the value into `out`, escaping `Text` with `escape`, and `Json` unless `escape`
+    /// is `Escape::Json`.
+    pub fn write_to<W: fmt::Write + ?Sized>(&self, out: &mut W, escape: Escape) -> fmt::Result {
+        match self {
+            Value::Text(text) => out.write_str(&escape.apply(text)),
+            Value::Raw(text) => out.write_str(text),
+            Value::Json(text) if escape == Escape::Json => out.write_str(text),
+            Value::Json(text) => out.write_str(&escape.apply(text)),
+            Value::Bool(value) => write!(out, "{value}"),
+            Value::Int(value) => write!(out, "{value}"),
+            Value::Float(value) => write!(out, "{value}"),
+            Value::Null | Value::Context(_) => Ok(()),
+            Value::List(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        out.write_str(", ")?;
+                    }
+                    item.write_to(out, escape)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the value one step down a dotted path: the entry `segment` of a nested context,
+    /// or the item at index `segment` of a list. Returns `None` for anything else.
+    pub fn field(self, segment: &str) -> Option<Value<'a>> {
+        match self {
+            Value::Context(context) => context.get(segment),
+            Value::List(items) => segment.parse().ok().and_then(|index| items.into_iter().nth(index)),
+            _ => None,
+        }
+    }
+
+    /// Returns `false` for empty text, `false`, zero, `Null` and empty lists, and `true`
+    /// otherwise. This decides which branch of an `{{#if}}` or `{{#unless}}` section is rendered.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Text(text) | Value::Raw(text) | Value::Json(text) => !text.is_empty(),
+            Value::Bool(value) => *value,
+            Value::Int(value) => *value != 0,
+            Value::Float(value) => *value != 0.0 && !value.is_nan(),
+            Value::Null => false,
+            Value::Context(_) => true,
+            Value::List(items) => !items.is_empty(),
+        }
+    }
+
+    /// Converts a borrowed value into one that owns its data, or returns `None` for a nested
+    /// context, which can only be borrowed.
+    pub fn into_owned(self) -> Option<Value<'static>> {
+        match self {
+            Value::Text(text) => Some(Value::Text(Cow::Owned(text.into_owned()))),
+            Value::Raw(text) => Some(Value::Raw(Cow::Owned(text.into_owned()))),
+            Value::Json(text) => Some(Value::Json(Cow::Owned(text.into_owned()))),
+            Value::Bool(value) => Some(Value::Bool(value)),
+            Value::Int(value) => Some(Value::Int(value)),
+            Value::Float(value) => Some(Value::Float(value)),
+            Value::Null => Some(Value::Null),
+            Value::Context(_) => None,
+            Value::List(items) => items.into_iter().map(Value::into_owned).collect::<Option<_>>().map(Value::List),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(text: &'a str) -> Self {
+        Value::Text(Cow::Borrowed(text))
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(text: String) -> Self {
+        Value::Text(Cow::Owned(text))
+    }
+}
+
+/// ### TemplateValue
+///
+/// Placeholder Value Conversion Trait
+///
+/// The `TemplateValue` trait converts a Rust value into the `Value` written for a placeholder.
+/// It is implemented for strings, characters, booleans, numbers, references, `Box`, `Rc`, `Arc`,
+/// `Raw`, `Option` (`None` becomes `Value::Null` and writes nothing), sequences
+/// (`Vec`, `VecDeque`, `BTreeSet`, `HashSet`, slices and arrays, as a `Value::List` written with `, `
+/// between items) and maps with string keys (as a nested context), and can be implemented for your
+/// own types; `#[derive(cans::Context)]` implements it for a struct
+/// so that it can be nested in another context. The content macros also accept any other type
+/// that implements `Display`. `Join` writes a sequence with another separator, and `Number`
+/// formats a number with a fixed precision and digit grouping.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::{TemplateValue, Value};
+///
+/// struct Price(u32);
+///
+/// impl TemplateValue for Price {
+///     fn to_value(&self) -> Value<'_> {
+///         Value::from(format!("${}.{:02}", self.0 / 100, self.0 % 100))
+///     }
+/// }
+///
+/// assert_eq!(Price(1250).to_value(), Value::from("$12.50"));
+/// ```
+/// <small>End Doc</small>
+pub trait TemplateValue {
+    /// Returns the value to write for a placeholder.
+    fn to_value(&self) -> Value<'_>;
+}
+
+impl<T: TemplateValue + ?Sized> TemplateValue for &T {
+    fn to_value(&self) -> Value<'_> {
+        (**self).to_value()
+    }
+}
+
+impl<T: TemplateValue + ?Sized> TemplateValue for Box<T> {
+    fn to_value(&self) -> Value<'_> {
+        (**self).to_value()
+    }
+}
+
+impl<T: TemplateValue + ?Sized> TemplateValue for Rc<T> {
+    fn to_value(&self) -> Value<'_> {
+        (**self).to_value()
+    }
+}
+
+impl<T: TemplateValue + ?Sized> TemplateValue for Arc<T> {
+    fn to_value(&self) -> Value<'_> {
+        (**self).to_value()
+    }
+}
+
+impl TemplateValue for str {
+    fn to_value(&self) -> Value<'_> {
+        Value::Text(Cow::Borrowed(self))
+    }
+}
+
+impl TemplateValue for String {
+    fn to_value(&self) -> Value<'_> {
+        Value::Text(Cow::Borrowed(self))
+    }
+}
+
+impl TemplateValue for Cow<'_, str> {
+    fn to_value(&self) -> Value<'_> {
+        Value::Text(Cow::Borrowed(self))
+    }
+}
+
+impl TemplateValue for Value<'_> {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            Value::Text(text) => Value::Text(Cow::Borrowed(text)),
+            Value::Raw(text) => Value::Raw(Cow::Borrowed(text)),
+            Value::Json(text) => Value::Json(Cow::Borrowed(text)),
+            Value::Bool(value) => Value::Bool(*value),
+            Value::Int(value) => Value::Int(*value),
+            Value::Float(value) => Value::Float(*value),
+            Value::Null => Value::Null,
+            Value::Context(context) => Value::Context(*context),
+            Value::List(items) => Value::List(items.iter().map(TemplateValue::to_value).collect()),
+        }
+    }
+}
+
+impl<T: fmt::Display> TemplateValue for Raw<T> {
+    fn to_value(&self) -> Value<'_> {
+        Value::Raw(Cow::Owned(self.0.to_string()))
+    }
+}
+
+impl<T: TemplateValue> TemplateValue for [T] {
+    fn to_value(&self) -> Value<'_> {
+        Value::List(self.iter().map(TemplateValue::to_value).collect())
+    }
+}
+
+impl<T: TemplateValue, const N: usize> TemplateValue for [T; N] {
+    fn to_value(&self) -> Value<'_> {
+        self.as_slice().to_value()
+    }
+}
+
+impl<T: TemplateValue> TemplateValue for Vec<T> {
+    fn to_value(&self) -> Value<'_> {
+        self.as_slice().to_value()
+    }
+}
+
+impl<T: TemplateValue> TemplateValue for VecDeque<T> {
+    fn to_value(&self) -> Value<'_> {
+        Value::List(self.iter().map(TemplateValue::to_value).collect())
+    }
+}
+
+impl<T: TemplateValue> TemplateValue for BTreeSet<T> {
+    fn to_value(&self) -> Value<'_> {
+        Value::List(self.iter().map(TemplateValue::to_value).collect())
+    }
+}
+
+impl<T: TemplateValue, S> TemplateValue for HashSet<T, S> {
+    /// The items are listed in the set's iteration order.
+    fn to_value(&self) -> Value<'_> {
+        Value::List(self.iter().map(TemplateValue::to_value).collect())
+    }
+}
+
+impl<K, V, S> TemplateValue for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    V: TemplateValue,
+    S: BuildHasher,
+{
+    fn to_value(&self) -> Value<'_> {
+        Value::Context(self)
+    }
+}
+
+impl<K, V> TemplateValue for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: TemplateValue,
+{
+    fn to_value(&self) -> Value<'_> {
+        Value::Context(self)
+    }
+}
+
+impl<T: TemplateValue> TemplateValue for Option<T> {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            Some(value) => value.to_value(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl TemplateValue for char {
+    fn to_value(&self) -> Value<'_> {
+        Value::Text(Cow::Owned(self.to_string()))
+    }
+}
+
+impl TemplateValue for bool {
+    fn to_value(&self) -> Value<'_> {
+        Value::Bool(*self)
+    }
+}
+
+impl TemplateValue for u128 {
+    /// Values above `i128::MAX` are kept as text.
+    fn to_value(&self) -> Value<'_> {
+        i128::try_from(*self).map_or_else(|_| Value::Text(Cow::Owned(self.to_string())), Value::Int)
+    }
+}
+
+impl TemplateValue for f32 {
+    /// Goes through the shortest decimal form, so `0.1_f32` is written as `0.1`.
+    fn to_value(&self) -> Value<'_> {
+        Value::Float(self.to_string().parse().unwrap_or(f64::NAN))
+    }
+}
+
+impl TemplateValue for f64 {
+    fn to_value(&self) -> Value<'_> {
+        Value::Float(*self)
+    }
+}
+
+macro_rules! integer_values {
+    ($($ty:ty),*) => {
+        $(
+            impl TemplateValue for $ty {
+                fn to_value(&self) -> Value<'_> {
+                    Value::Int(*self as i128)
+                }
+            }
+        )*
+    };
+}
+
+integer_values!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, usize);
+
+/// ### Join(values, separator)
+///
+/// Sequence Separator Wrapper
+///
+/// The `Join` wrapper writes the items of a sequence, or of any value that converts to a
+/// `Value::List`, separated by `separator` instead of `, `, like the `join` filter does in a
+/// template. The joined text is escaped like any other text, separator included.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::Join;
+/// use cans::do_html;
+///
+/// let tags = vec!["rust", "<web>"];
+/// assert_eq!(do_html!("<p>{{tags}}</p>", tags = Join(&tags, " / ")), "<p>rust / &lt;web&gt;</p>");
+/// assert_eq!(do_html!("<p>{{tags}}</p>", tags = &tags), "<p>rust, &lt;web&gt;</p>");
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Join<'s, T>(pub T, pub &'s str);
+
+impl<T: TemplateValue> TemplateValue for Join<'_, T> {
+    fn to_value(&self) -> Value<'_> {
+        filter::join(self.0.to_value(), self.1)
+    }
+}
+
+impl<T: TemplateValue> fmt::Display for Join<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_value().fmt(f)
+    }
+}
+
+/// ### Number
+///
+/// Number Formatting Wrapper
+///
+/// A `Number` formats an integer, a float, or text holding a number with a fixed number of
+/// decimal places and digit grouping, like the `number` filter does in a template. By default
+/// it keeps as many decimal places as needed, does not group digits and uses `.` as the
+/// decimal point. Values that are not numbers are written unchanged.
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::Number;
+/// use cans::do_replace;
+///
+/// let total = Number::new(1234567.891).precision(2).thousands(',');
+/// assert_eq!(do_replace!("Total: {{total}}", total = total), "Total: 1,234,567.89");
+///
+/// assert_eq!(Number::new(-9876543).thousands('.').to_string(), "-9.876.543");
+/// assert_eq!(Number::new(0.1 + 0.2).precision(1).to_string(), "0.3");
+/// assert_eq!(Number::new(1234.5).precision(2).thousands(' ').decimal_point(',').to_string(), "1 234,50");
+/// ```
+/// <small>End Doc</small>
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number<T> {
+    value: T,
+    precision: Option<usize>,
+    thousands: Option<char>,
+    decimal_point: char,
+}
+
+impl<T: TemplateValue> Number<T> {
+    /// Wraps `value` with the default format.
+    pub fn new(value: T) -> Self {
+        Number { value, precision: None, thousands: None, decimal_point: '.' }
+    }
+
+    /// Writes exactly `places` decimal places, rounding the value.
+    pub fn precision(mut self, places: usize) -> Self {
+        self.precision = Some(places);
+        self
+    }
+
+    /// Groups the digits before the decimal point in threes, separated by `separator`.
+    pub fn thousands(mut self, separator: char) -> Self {
+        self.thousands = Some(separator);
+        self
+    }
+
+    /// Writes `decimal_point` between the whole number and its decimal places.
+    pub fn decimal_point(mut self, decimal_point: char) -> Self {
+        self.decimal_point = decimal_point;
+        self
+    }
+}
+
+impl<T: TemplateValue> TemplateValue for Number<T> {
+    fn to_value(&self) -> Value<'_> {
+        let value = self.value.to_value();
+        let (mut thousands, mut decimal_point) = ([0; 4], [0; 4]);
+        let thousands = self.thousands.map_or("", |separator| separator.encode_utf8(&mut thousands));
+        let decimal_point = self.decimal_point.encode_utf8(&mut decimal_point);
+        match filter::format_number(&value, self.precision, thousands, decimal_point) {
+            Ok(text) => Value::Text(Cow::Owned(text)),
+            Err(_) => value,
+        }
+    }
+}
+
+impl<T: TemplateValue> fmt::Display for Number<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_value().fmt(f)
+    }
+}
+
+/// ### Lazy
+///
+/// Lazily Evaluated Value
+///
+/// A `Lazy` value runs its closure only when a template looks it up, and keeps the result, so
+/// a placeholder that appears several times, or in several templates rendered with the same
+/// context, runs it once. Use it for values that are expensive to produce, such as rendered
+/// sub-tables or snippets loaded from a database: when the template does not refer to the
+/// placeholder, or only refers to it in a section that is not rendered, the closure is never
+/// called. The closure is called at most once, so it may move what it captures into the
+/// result. A `Lazy` value can be passed to the content macros and stored in any `Context`.
+///
+/// ### Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use cans::content::{render_with, Lazy};
+///
+/// let queries = Cell::new(0);
+/// let load_table = || {
+///     queries.set(queries.get() + 1);
+///     "<table>...</table>".to_string()
+/// };
+///
+/// let context = [("table", Lazy::new(load_table))];
+/// assert_eq!(render_with("<p>No table here</p>", &context), "<p>No table here</p>");
+/// assert_eq!(queries.get(), 0);
+///
+/// assert_eq!(render_with("{{table}}{{table}}", &context), "<table>...</table><table>...</table>");
+/// assert_eq!(queries.get(), 1);
+///
+/// let rows = vec!["a", "b"];
+/// let list = Lazy::new(move || rows.into_iter().map(|row| format!("<li>{row}</li>")).collect::<String>());
+/// assert_eq!(render_with("<ul>{{list}}</ul>", &[("list", list)]), "<ul><li>a</li><li>b</li></ul>");
+/// ```
+/// <small>End Doc</small>
+pub struct Lazy<F, T> {
+    init: Mutex<Option<F>>,
+    value: OnceLock<T>,
+}
+
+impl<F: FnOnce() -> T, T> Lazy<F, T> {
+    /// Wraps `init`, which is called the first time the value is looked up.
+    pub fn new(init: F) -> Self {
+        Lazy { init: Mutex::new(Some(init)), value: OnceLock::new() }
+    }
+
+    /// Returns the value, calling the closure if it has not been called yet.
+    ///
+    /// ### Panics
+    /// Panics if the closure panicked on an earlier call.
+    pub fn get(&self) -> &T {
+        self.value.get_or_init(|| {
+            let init = self.init.lock().unwrap_or_else(PoisonError::into_inner).take();
+            init.expect("the `Lazy` closure panicked on an earlier call")()
+        })
+    }
+
+    /// Returns `true` if the closure has been called.
+    pub fn is_evaluated(&self) -> bool {
+        self.value.get().is_some()
+    }
+}
+
+impl<F: FnOnce() -> T, T: TemplateValue> TemplateValue for Lazy<F, T> {
+    fn to_value(&self) -> Value<'_> {
+        self.get().to_value()
+    }
+}
+
+impl<F, T: fmt::Debug> fmt::Debug for Lazy<F, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some(value) => f.debug_tuple("Lazy").field(value).finish(),
+            None => f.write_str("Lazy(..)"),
+        }
+    }
+}
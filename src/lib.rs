@@ -10,6 +10,12 @@
 /// A set of functions and macros for generating and manipulating various content.
 pub mod content;
 
+/// ### Context Derive Macro
+///
+/// `#[derive(cans::Context)]` exposes every field of a struct as a template placeholder.
+/// See `cans::content::Context`.
+pub use cans_macros::{embed_templates, Context};
+
 /// ### Mime Module
 ///
 /// A set of functions for managing MIME types using a HashMap.
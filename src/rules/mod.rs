@@ -1,3 +1,14 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+
+use crate::content::parser::{bytes_eq, next_arg, next_filter, next_tag, path_root, references_key, Delims, TagKind, TagSpan};
+use crate::content::{Context, Delimiters, Escape, Issue, IssueKind, RenderError, Template, TemplateValue, Value};
+
+#[doc(hidden)]
+pub use cans_macros::check_template;
+
 /// ### do_replace!($content, $key, and $val)
 ///
 /// Macro Rules
@@ -5,6 +16,7 @@
 /// The `do_replace` macro takes any content string or data (e.g., code, file content, templates)
 /// along with key-value pairs and replaces placeholders in the content (formatted as `{{key}}`) with the corresponding values.
 /// This macro is highly versatile and can manipulate any content of any programming language or file by substituting placeholders.
+/// `do_replace!` inserts values unchanged; use `do_html!`, `do_xml!` or `do_json!` when values must be escaped.
 ///
 /// ### Parameters
 /// - `$content`: The content string containing placeholders for substitution (e.g., `"<p>Hello, {{name}}!</p>"`).
@@ -23,6 +35,50 @@
 /// 
 /// This macro can be used to update any content dynamically by replacing placeholders with actual values.
 ///
+/// ### Spreading a Context
+///
+/// A trailing `..context` argument supplies every placeholder that has no explicit argument from any
+/// `cans::content::Context`, such as a struct with `#[derive(cans::Context)]` or a `HashMap`:
+/// ```rust
+/// use cans::do_replace;
+///
+/// #[derive(cans::Context)]
+/// struct User {
+///     name: String,
+///     city: String,
+/// }
+///
+/// let user = User { name: "Sara".into(), city: "Cairo".into() };
+/// assert_eq!(do_replace!("{{name}} from {{city}}", ..user), "Sara from Cairo");
+/// assert_eq!(do_replace!("{{name}} from {{city}}", city = "Rome", ..user), "Sara from Rome");
+/// ```
+///
+/// ### Compile-Time Checks
+///
+/// When the content is a string literal or a `const` written in `SCREAMING_CASE` (such as
+/// `HOME_TEMPLATE` or `pages::HOME_TEMPLATE`), the template is checked while compiling. The build fails if
+/// a `{{placeholder}}` has no matching argument (unless a `..context` is spread), if an argument matches no placeholder, or if the same key
+/// is passed twice, and the error points at the template or at the offending key. Templates held in other
+/// expressions (variables, function calls, `String`s) are only known at run time and are not checked.
+/// This applies to `do_html!`, `do_xml!` and `do_json!` as well.
+/// ```rust,compile_fail
+/// use cans::do_html;
+///
+/// const HOME_TEMPLATE: &str = "<title>{{page_title}}</title>";
+///
+/// // error: argument `page_tilte` matches no placeholder in the template
+/// let page = do_html!(HOME_TEMPLATE, page_tilte = "Home");
+/// ```
+///
+/// The content is scanned once and every placeholder is filled exactly once, so the result does
+/// not depend on the order of the arguments and a value containing `{{other_key}}` is never expanded:
+/// ```rust
+/// use cans::do_replace;
+///
+/// let result = do_replace!("{{first}} / {{second}}", first = "{{second}}", second = "2");
+/// assert_eq!(result, "{{second}} / 2");
+/// ```
+///
 /// #### Example: Creating a new macro using `do_replace!`
 ///
 /// Macros for specific content types can be defined by wrapping `do_replace!`.
@@ -32,31 +88,165 @@
 /// use cans::do_replace;
 /// 
 /// #[macro_export]
-/// macro_rules! do_html {
+/// macro_rules! do_css {
 ///     ($content:expr, $($key:ident = $val:expr),*) => {
 ///         $crate::do_replace!($content, $($key = $val),*)
 ///     };
 /// }
 /// ```
 /// Now, `do_css!` can be used to easily replace placeholders in CSS templates.
+///
+/// A wrapper can also pick an escaping mode by forwarding to the `@escape` form, which is how
+/// `do_html!`, `do_xml!` and `do_json!` are defined:
+/// ```rust
+/// use cans::do_replace;
+///
+/// macro_rules! do_svg {
+///     ($content:expr, $($key:ident = $val:expr),*) => {
+///         do_replace!(@escape cans::content::Escape::Xml, $content, $($key = $val),*)
+///     };
+/// }
+///
+/// let svg = do_svg!("<text>{{label}}</text>", label = "a < b");
+/// assert_eq!(svg, "<text>a &lt; b</text>");
+/// ```
+///
+/// #### Example: Custom delimiters
+///
+/// `@delimiters(open, close)` replaces `{{` and `}}` for templates that already use them, and
+/// may follow `@escape`. Literal and `const` templates are still checked at compile time when the
+/// delimiters are literals or constants. A `{{"{{"}}` literal tag writes the opening delimiter as is.
+/// ```rust
+/// use cans::do_replace;
+///
+/// let vue = do_replace!(@delimiters("[[", "]]"), "<p>{{ message }}</p><p>[[count]]</p>", count = 3);
+/// assert_eq!(vue, "<p>{{ message }}</p><p>3</p>");
+///
+/// let xml = do_replace!(@escape cans::content::Escape::Xml, @delimiters("${", "}"), "<a>${name}</a>", name = "<b>");
+/// assert_eq!(xml, "<a>&lt;b&gt;</a>");
+///
+/// assert_eq!(do_replace!(r#"{{"{{"}}name}} = {{name}}"#, name = "x"), "{{name}} = x");
+/// ```
+///
+/// #### Example: Streaming into a sink
+///
+/// `@write(out)` renders straight into `out`, a mutable reference to an `io::Write` sink such
+/// as a file, a socket or a response body, or to a `fmt::Write` sink such as a `String`, instead
+/// of returning a new `String`. It returns the sink's `io::Result<()>` or `fmt::Result`, and
+/// may follow `@escape` and `@delimiters`.
+/// ```rust
+/// use cans::{do_html, do_replace};
+///
+/// let mut body: Vec<u8> = Vec::new();
+/// do_html!(@write(&mut body), "<h1>{{title}}</h1>", title = "A & B").unwrap();
+/// assert_eq!(body, b"<h1>A &amp; B</h1>");
+///
+/// let mut page = String::from("<!DOCTYPE html>");
+/// do_replace!(@write(&mut page), "<p>{{n}}</p>", n = 3).unwrap();
+/// assert_eq!(page, "<!DOCTYPE html><p>3</p>");
+/// ```
+///
+/// #### Example: Indentation-aware substitution
+///
+/// With `@indent`, every line of a multi-line value after the first is indented to the column of
+/// its placeholder, which keeps generated YAML, Python or Rust source well formed. Empty lines
+/// stay empty. The options go in the order `@delimiters`, `@indent`, `@write`.
+/// ```rust
+/// use cans::do_replace;
+///
+/// let body = "let x = 1;\nx + 1";
+/// let code = do_replace!(@indent, "fn answer() -> i32 {\n    {{body}}\n}", body = body);
+/// assert_eq!(code, "fn answer() -> i32 {\n    let x = 1;\n    x + 1\n}");
+///
+/// let yaml = do_replace!(@indent, "script: |\n  {{script}}", script = "cargo build\ncargo test");
+/// assert_eq!(yaml, "script: |\n  cargo build\n  cargo test");
+/// ```
 /// 
 /// <small>End Doc</small>
 #[macro_export]
 macro_rules! do_replace {
-    // Accept a content block and key-value pairs for substitution
-    ($content:expr, $($key:ident = $val:expr),*) => {{
-        let mut content_string = $content.to_string();
-
-        // Iterate over all key-value pairs and replace placeholders
-        $(
-            // Replace placeholders like `{{name}}` in the content string
-            let val = format!("{}", $val);
-            content_string = content_string.replace(&format!("{{{{{}}}}}", stringify!($key)), &val);
-        )*
-
-        // Return the processed content string
-        content_string
+    // Stream the result into `$out` over a spread context (`..context`)
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent($indent:expr), @write($out:expr), $content:expr, $($key:ident = $val:expr,)* .. $context:expr $(,)?) => {{
+        $crate::rules::check_template!($crate; $content; $($key),*; $open, $close; ..);
+
+        #[allow(unused_imports)]
+        use $crate::rules::{__ViaDisplay, __ViaTemplateValue, __ViaFmtWrite, __ViaIoWrite};
+
+        // `io::Write` sinks are preferred over `fmt::Write` sinks
+        (&&$crate::rules::__Sink::new($out)).__cans_write(&$content, $crate::rules::__Settings { escape: $escape, delimiters: ($open, $close), indent: $indent }, &$crate::rules::__Layered::new(&[
+            $((stringify!($key), (&&$crate::rules::__Arg(&$val)).__cans_value())),*
+        ], &$context))
     }};
+    // Stream the result into `$out`, an `io::Write` or `fmt::Write` sink
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent($indent:expr), @write($out:expr), $content:expr, $($key:ident = $val:expr),* $(,)?) => {{
+        $crate::rules::check_template!($crate; $content; $($key),*; $open, $close);
+
+        #[allow(unused_imports)]
+        use $crate::rules::{__ViaDisplay, __ViaTemplateValue, __ViaFmtWrite, __ViaIoWrite};
+
+        (&&$crate::rules::__Sink::new($out)).__cans_write(&$content, $crate::rules::__Settings { escape: $escape, delimiters: ($open, $close), indent: $indent }, &[
+            $((stringify!($key), (&&$crate::rules::__Arg(&$val)).__cans_value())),*
+        ])
+    }};
+    // Substitute key-value pairs over a spread context (`..context`), escaping with the given mode
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent($indent:expr), $content:expr, $($key:ident = $val:expr,)* .. $context:expr $(,)?) => {{
+        // Check literal and `const` templates against the keys at compile time
+        $crate::rules::check_template!($crate; $content; $($key),*; $open, $close; ..);
+
+        // Values implementing `TemplateValue` are used as they are, others through `Display`
+        #[allow(unused_imports)]
+        use $crate::rules::{__ViaDisplay, __ViaTemplateValue};
+
+        // Explicit pairs take precedence over the spread context
+        $crate::rules::__render_with(&$content, $crate::rules::__Settings { escape: $escape, delimiters: ($open, $close), indent: $indent }, &[
+            $((stringify!($key), (&&$crate::rules::__Arg(&$val)).__cans_value())),*
+        ], &$context)
+    }};
+    // Substitute key-value pairs, escaping each value with the given `Escape` mode
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent($indent:expr), $content:expr, $($key:ident = $val:expr),* $(,)?) => {{
+        // Check literal and `const` templates against the keys at compile time
+        $crate::rules::check_template!($crate; $content; $($key),*; $open, $close);
+
+        // Values implementing `TemplateValue` are used as they are, others through `Display`
+        #[allow(unused_imports)]
+        use $crate::rules::{__ViaDisplay, __ViaTemplateValue};
+
+        // Parse the content into a `Template` and fill every placeholder in a single pass
+        $crate::rules::__render(&$content, $crate::rules::__Settings { escape: $escape, delimiters: ($open, $close), indent: $indent }, &[
+            $((stringify!($key), (&&$crate::rules::__Arg(&$val)).__cans_value())),*
+        ])
+    }};
+    // Re-indent multi-line values to the column of their placeholder
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent, $($args:tt)*) => {
+        $crate::do_replace!(@escape $escape, @delimiters($open, $close), @indent(true), $($args)*)
+    };
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), $($args:tt)*) => {
+        $crate::do_replace!(@escape $escape, @delimiters($open, $close), @indent(false), $($args)*)
+    };
+    // Use the default `{{` and `}}` delimiters
+    (@escape $escape:expr, $($args:tt)*) => {
+        $crate::do_replace!(@escape $escape, @delimiters("{{", "}}"), $($args)*)
+    };
+    // Accept custom delimiters without escaping
+    (@delimiters($open:expr, $close:expr), $($args:tt)*) => {
+        $crate::do_replace!(@escape $crate::content::Escape::None, @delimiters($open, $close), $($args)*)
+    };
+    // Accept the indentation mode without escaping
+    (@indent, $($args:tt)*) => {
+        $crate::do_replace!(@escape $crate::content::Escape::None, @indent, $($args)*)
+    };
+    // Accept a sink without escaping
+    (@write($out:expr), $($args:tt)*) => {
+        $crate::do_replace!(@escape $crate::content::Escape::None, @write($out), $($args)*)
+    };
+    // Accept a content block, key-value pairs and a spread context
+    ($content:expr, $($key:ident = $val:expr,)* .. $context:expr $(,)?) => {
+        $crate::do_replace!(@escape $crate::content::Escape::None, $content, $($key = $val,)* ..$context)
+    };
+    // Accept a content block and key-value pairs for substitution
+    ($content:expr, $($key:ident = $val:expr),* $(,)?) => {
+        $crate::do_replace!(@escape $crate::content::Escape::None, $content, $($key = $val),*)
+    };
 }
 
 /// ### do_html!($content, $key, and $val)
@@ -66,6 +256,10 @@ macro_rules! do_replace {
 /// The `do_html` macro takes HTML string content along with key-value pairs and replaces placeholders in the HTML (formatted as `{{key}}`) with the corresponding values.
 /// It returns the processed HTML string with the substitutions applied.
 ///
+/// Values are HTML-escaped (`&`, `<`, `>`, `"` and `'` become entities), so user-supplied text
+/// can never inject markup. Wrap trusted markup, such as a shared `HEAD` partial, in
+/// `cans::content::Raw` to insert it unchanged.
+///
 /// ### Parameters
 /// - `$content`: The HTML string content containing placeholders for substitution (e.g., `"<p>Hello, {{name}}!</p>"`).
 /// - `$key`: The identifier for each placeholder in the HTML (e.g., `name`).
@@ -79,15 +273,101 @@ macro_rules! do_replace {
 /// let template = "<p>Hello, {{name}}! Welcome to {{place}}.</p>";
 /// let result = do_html!(template, name = "Dear", place = "CANS Template");
 /// assert_eq!(result, "<p>Hello, Dear! Welcome to CANS Template.</p>");
+///
+/// let escaped = do_html!(template, name = "<script>", place = "Tom & Jerry's");
+/// assert_eq!(escaped, "<p>Hello, &lt;script&gt;! Welcome to Tom &amp; Jerry&#x27;s.</p>");
+/// ```
+///
+/// #### Example: Inserting trusted markup with `Raw`
+/// ```rust
+/// use cans::do_html;
+/// use cans::content::{alpine, Raw};
+///
+/// let template = "<head>{{script}}<title>{{title}}</title></head>";
+/// let result = do_html!(template, script = Raw(alpine("latest")), title = "A < B");
+/// assert_eq!(
+///     result,
+///     r#"<head><script defer src="https://unpkg.com/alpinejs@latest/dist/cdn.min.js"></script><title>A &lt; B</title></head>"#
+/// );
 /// ```
 /// <small>End Doc</small>
 #[macro_export]
 macro_rules! do_html {
-    ($content:expr, $($key:ident = $val:expr),*) => {
-        $crate::do_replace!($content, $($key = $val),*)
+    ($($args:tt)*) => {
+        $crate::do_replace!(@escape $crate::content::Escape::Html, $($args)*)
+    };
+}
+
+/// ### do_html_file!($path, $key, and $val)
+///
+/// Macro Rules
+///
+/// The `do_html_file` macro works like `do_html!`, but reads the template from a file that is
+/// embedded into the binary at compile time. The path is relative to the crate root, where
+/// `Cargo.toml` is. The template is checked at compile time like a literal template: a
+/// placeholder without an argument or an argument without a placeholder fails the build, and
+/// editing the file triggers a rebuild. Use a `TemplateLoader` to read templates at run time.
+///
+/// ### Parameters
+/// - `$path`: The path of the template file as a string literal (e.g., `"templates/home.html"`).
+/// - `$key`, `$val`: As for `do_html!`, including a trailing `..context` spread.
+///
+/// ### Examples
+/// ```rust
+/// use cans::do_html_file;
+///
+/// // tests/templates/partials/card.html: <article>{{title}}</article>
+/// let card = do_html_file!("tests/templates/partials/card.html", title = "Fish & Chips");
+/// assert_eq!(card, "<article>Fish &amp; Chips</article>");
+/// ```
+///
+/// ```rust,compile_fail
+/// use cans::do_html_file;
+///
+/// // error: no argument supplies the placeholder `{{title}}`
+/// let card = do_html_file!("tests/templates/partials/card.html", headline = "News");
+/// ```
+/// <small>End Doc</small>
+#[macro_export]
+macro_rules! do_html_file {
+    ($path:literal $(, $($args:tt)*)?) => {
+        $crate::do_html!(include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $path)), $($($args)*)?)
     };
 }
 
+/// ### template_loader!($dir)
+///
+/// Macro Rules
+///
+/// The `template_loader` macro creates a `TemplateLoader` for the templates below `$dir`, a
+/// directory relative to the crate root. In debug builds the templates are read from the
+/// directory and reloaded when their files change, so they can be edited without rebuilding.
+/// In release builds they are embedded into the binary with `embed_templates!` and the
+/// directory is not needed at run time. It returns an `io::Result<TemplateLoader>`, which is
+/// an error in debug builds if the directory cannot be read.
+///
+/// ### Parameters
+/// - `$dir`: The template directory as a string literal (e.g., `"templates"`).
+///
+/// ### Examples
+/// ```rust
+/// use cans::template_loader;
+///
+/// let loader = template_loader!("tests/templates").unwrap();
+/// assert_eq!(loader.render("partials/card.html", &[("title", "News")]).unwrap(), "<article>News</article>");
+/// ```
+/// <small>End Doc</small>
+#[macro_export]
+macro_rules! template_loader {
+    ($dir:literal) => {{
+        #[cfg(debug_assertions)]
+        let loader = $crate::content::TemplateLoader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir));
+        #[cfg(not(debug_assertions))]
+        let loader = ::std::io::Result::Ok($crate::content::TemplateLoader::from_embedded($crate::embed_templates!($dir)));
+        loader
+    }};
+}
+
 /// ### do_xml!($content, $key, and $val)
 ///
 /// Macro Rules
@@ -95,6 +375,9 @@ macro_rules! do_html {
 /// The `do_xml` macro takes XML string content along with key-value pairs and replaces placeholders in the XML (formatted as `{{key}}`) with the corresponding values.
 /// It returns the processed XML string with the substitutions applied.
 ///
+/// Values are escaped with the predefined XML entities (`&amp;`, `&lt;`, `&gt;`, `&quot;` and `&apos;`).
+/// Wrap trusted markup in `cans::content::Raw` to insert it unchanged.
+///
 /// ### Parameters
 /// - `$content`: The XML string content containing placeholders for substitution (e.g., `"<p>Hello, {{name}}!</p>"`).
 /// - `$key`: The identifier for each placeholder in the XML (e.g., `name`).
@@ -108,12 +391,15 @@ macro_rules! do_html {
 /// let xml_content = "<note><to>{{recipient}}</to></note>";
 /// let xml_result = do_xml!(xml_content, recipient = "Ahmed");
 /// assert_eq!(xml_result, "<note><to>Ahmed</to></note>");
+///
+/// let escaped = do_xml!(xml_content, recipient = "Tom & 'Jerry'");
+/// assert_eq!(escaped, "<note><to>Tom &amp; &apos;Jerry&apos;</to></note>");
 /// ```
 /// <small>End Doc</small>
 #[macro_export]
 macro_rules! do_xml {
-    ($content:expr, $($key:ident = $val:expr),*) => {
-        $crate::do_replace!($content, $($key = $val),*)
+    ($($args:tt)*) => {
+        $crate::do_replace!(@escape $crate::content::Escape::Xml, $($args)*)
     };
 }
 
@@ -124,12 +410,16 @@ macro_rules! do_xml {
 /// The `do_json` macro takes a JSON string with placeholders and replaces them with provided values.
 /// It is an example of creating a specific macro wrapper for JSON content using `do_replace!`.
 ///
+/// Values are escaped as JSON string content (quotes, backslashes and control characters are
+/// backslash-escaped), so placeholders should sit between quotes in the template.
+/// Wrap pre-built JSON fragments in `cans::content::Raw` to insert them unchanged.
+///
 /// ### Parameters
 /// - `$content`: The JSON string containing placeholders (e.g., `"{\"name\": \"{{name}}\"}"`).
 /// - `$key`: The placeholder identifier (e.g., `name`).
 /// - `$val`: The value to replace the placeholder (e.g., `"Ahmed"`).
 /// 
-//// ### Examples
+/// ### Examples
 /// 
 /// ```rust
 /// // use cans::content::do_json;
@@ -138,12 +428,468 @@ macro_rules! do_xml {
 /// let json_content = r##"{"greeting": "{{greeting}}", "name": "{{name}}"}"##;
 /// let json_result = do_json!(json_content, greeting = "Hi", name = "Ahmed");
 /// assert_eq!(json_result, r##"{"greeting": "Hi", "name": "Ahmed"}"##);
+///
+/// let escaped = do_json!(json_content, greeting = "Hi", name = "\"Ahmed\"\n");
+/// assert_eq!(escaped, r##"{"greeting": "Hi", "name": "\"Ahmed\"\n"}"##);
 /// ```
 /// 
 /// <small>End Doc</small>
 #[macro_export]
 macro_rules! do_json {
-    ($content:expr, $($key:ident = $val:expr),*) => {
-        $crate::do_replace!($content, $($key = $val),*)
+    ($($args:tt)*) => {
+        $crate::do_replace!(@escape $crate::content::Escape::Json, $($args)*)
+    };
+}
+
+/// ### try_do_replace!($content, $key, and $val)
+///
+/// Macro Rules
+///
+/// The `try_do_replace` macro works like `do_replace!`, but returns a
+/// `Result<String, cans::content::RenderError>` instead of a `String`. Rendering fails when
+/// anything would be left unresolved: a placeholder without a value, a malformed tag such as
+/// `{{ name }}`, a misplaced or unclosed section, or an argument that matches no placeholder.
+/// The error lists every problem at once, each with its byte offsets, line and column, so
+/// snapshot tests and CI can fail fast. `try_do_html!`, `try_do_xml!` and `try_do_json!` are
+/// the escaping variants, and all of them accept a trailing `..context` like `do_replace!`.
+///
+/// Templates given as a string literal or a `SCREAMING_CASE` constant are also checked at
+/// compile time; the runtime checks cover templates that are only known at run time.
+///
+/// ### Parameters
+/// - `$content`: The content string containing placeholders for substitution (e.g., `"<p>Hello, {{name}}!</p>"`).
+/// - `$key`: The identifier for each placeholder in the content (e.g., `name`).
+/// - `$val`: The value that replaces the corresponding placeholder in the content (e.g., `"Alice"`).
+///
+/// ### Examples
+/// ```rust
+/// use cans::content::IssueKind;
+/// use cans::try_do_replace;
+///
+/// let greeting = try_do_replace!("Hello, {{name}}!", name = "Dear");
+/// assert_eq!(greeting.unwrap(), "Hello, Dear!");
+///
+/// // A template loaded at run time.
+/// let template = String::from("Hello, {{name}}!\nWelcome to {{place}} {{ year }}.");
+/// let error = try_do_replace!(template, name = "Dear", city = "Cairo").unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     "template could not be rendered:\n\
+///      - `{{place}}` (line 2, column 12, bytes 28..37): the placeholder has no value\n\
+///      - `{{ year }}` (line 2, column 22, bytes 38..48): the tag is not a valid placeholder or section tag\n\
+///      - argument `city` matches no placeholder in the template"
+/// );
+/// assert!(matches!(error.issues()[2].kind, IssueKind::UnusedArgument { .. }));
+/// ```
+/// <small>End Doc</small>
+#[macro_export]
+macro_rules! try_do_replace {
+    // Substitute key-value pairs over a spread context (`..context`), escaping with the given mode
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent($indent:expr), $content:expr, $($key:ident = $val:expr,)* .. $context:expr $(,)?) => {{
+        $crate::rules::check_template!($crate; $content; $($key),*; $open, $close; ..);
+
+        #[allow(unused_imports)]
+        use $crate::rules::{__ViaDisplay, __ViaTemplateValue};
+
+        $crate::rules::__try_render_with(&$content, $crate::rules::__Settings { escape: $escape, delimiters: ($open, $close), indent: $indent }, &[
+            $((stringify!($key), (&&$crate::rules::__Arg(&$val)).__cans_value())),*
+        ], &$context)
+    }};
+    // Substitute key-value pairs, escaping each value with the given `Escape` mode
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent($indent:expr), $content:expr, $($key:ident = $val:expr),* $(,)?) => {{
+        $crate::rules::check_template!($crate; $content; $($key),*; $open, $close);
+
+        #[allow(unused_imports)]
+        use $crate::rules::{__ViaDisplay, __ViaTemplateValue};
+
+        $crate::rules::__try_render(&$content, $crate::rules::__Settings { escape: $escape, delimiters: ($open, $close), indent: $indent }, &[
+            $((stringify!($key), (&&$crate::rules::__Arg(&$val)).__cans_value())),*
+        ])
+    }};
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), @indent, $($args:tt)*) => {
+        $crate::try_do_replace!(@escape $escape, @delimiters($open, $close), @indent(true), $($args)*)
+    };
+    (@escape $escape:expr, @delimiters($open:expr, $close:expr), $($args:tt)*) => {
+        $crate::try_do_replace!(@escape $escape, @delimiters($open, $close), @indent(false), $($args)*)
+    };
+    (@escape $escape:expr, $($args:tt)*) => {
+        $crate::try_do_replace!(@escape $escape, @delimiters("{{", "}}"), $($args)*)
+    };
+    (@indent, $($args:tt)*) => {
+        $crate::try_do_replace!(@escape $crate::content::Escape::None, @indent, $($args)*)
+    };
+    (@delimiters($open:expr, $close:expr), $($args:tt)*) => {
+        $crate::try_do_replace!(@escape $crate::content::Escape::None, @delimiters($open, $close), $($args)*)
+    };
+    ($content:expr, $($key:ident = $val:expr,)* .. $context:expr $(,)?) => {
+        $crate::try_do_replace!(@escape $crate::content::Escape::None, $content, $($key = $val,)* ..$context)
     };
-}
\ No newline at end of file
+    ($content:expr, $($key:ident = $val:expr),* $(,)?) => {
+        $crate::try_do_replace!(@escape $crate::content::Escape::None, $content, $($key = $val),*)
+    };
+}
+
+/// ### try_do_html!($content, $key, and $val)
+///
+/// Macro Rules
+///
+/// The `try_do_html` macro is the fallible form of `do_html!`: values are HTML-escaped, and a
+/// `RenderError` is returned instead of a page with unresolved placeholders. See
+/// `try_do_replace!` for the problems it reports.
+///
+/// ### Examples
+/// ```rust
+/// use cans::try_do_html;
+///
+/// let page = try_do_html!("<h1>{{title}}</h1>", title = "Fish & Chips");
+/// assert_eq!(page.unwrap(), "<h1>Fish &amp; Chips</h1>");
+///
+/// let template = "<h1>{{title}}</h1><p>{{body}}</p>".to_string();
+/// assert!(try_do_html!(template, title = "Home").is_err());
+/// ```
+/// <small>End Doc</small>
+#[macro_export]
+macro_rules! try_do_html {
+    ($($args:tt)*) => {
+        $crate::try_do_replace!(@escape $crate::content::Escape::Html, $($args)*)
+    };
+}
+
+/// ### try_do_xml!($content, $key, and $val)
+///
+/// Macro Rules
+///
+/// The `try_do_xml` macro is the fallible form of `do_xml!`. See `try_do_replace!` for the
+/// problems it reports.
+///
+/// ### Examples
+/// ```rust
+/// use cans::try_do_xml;
+///
+/// let note = try_do_xml!("<note><to>{{recipient}}</to></note>", recipient = "Tom & Jerry");
+/// assert_eq!(note.unwrap(), "<note><to>Tom &amp; Jerry</to></note>");
+/// ```
+/// <small>End Doc</small>
+#[macro_export]
+macro_rules! try_do_xml {
+    ($($args:tt)*) => {
+        $crate::try_do_replace!(@escape $crate::content::Escape::Xml, $($args)*)
+    };
+}
+
+/// ### try_do_json!($content, $key1 = $val1, $key2 = $val2, ...)
+///
+/// Macro Rules
+///
+/// The `try_do_json` macro is the fallible form of `do_json!`. See `try_do_replace!` for the
+/// problems it reports.
+///
+/// ### Examples
+/// ```rust
+/// use cans::try_do_json;
+///
+/// let json = try_do_json!(r#"{"name": "{{name}}"}"#, name = "\"Ahmed\"");
+/// assert_eq!(json.unwrap(), r#"{"name": "\"Ahmed\""}"#);
+/// ```
+/// <small>End Doc</small>
+#[macro_export]
+macro_rules! try_do_json {
+    ($($args:tt)*) => {
+        $crate::try_do_replace!(@escape $crate::content::Escape::Json, $($args)*)
+    };
+}
+
+// Support for the content macros: `__Arg` picks `TemplateValue` over `Display` for each value
+// and `__Sink` picks `io::Write` over `fmt::Write` for `@write` through auto-ref method
+// resolution, and `__render` renders the collected pairs.
+
+#[doc(hidden)]
+pub struct __Arg<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub enum __Value<'a> {
+    Value(&'a dyn TemplateValue),
+    Display(&'a dyn fmt::Display),
+}
+
+impl TemplateValue for __Value<'_> {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            __Value::Value(value) => value.to_value(),
+            __Value::Display(value) => Value::Text(Cow::Owned(value.to_string())),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub trait __ViaTemplateValue<'a> {
+    fn __cans_value(&self) -> __Value<'a>;
+}
+
+impl<'a, T: TemplateValue> __ViaTemplateValue<'a> for &__Arg<'a, T> {
+    fn __cans_value(&self) -> __Value<'a> {
+        __Value::Value(self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait __ViaDisplay<'a> {
+    fn __cans_value(&self) -> __Value<'a>;
+}
+
+impl<'a, T: fmt::Display> __ViaDisplay<'a> for __Arg<'a, T> {
+    fn __cans_value(&self) -> __Value<'a> {
+        __Value::Display(self.0)
+    }
+}
+
+/// The settings of the template of a content macro.
+#[doc(hidden)]
+pub struct __Settings<'a> {
+    pub escape: Escape,
+    pub delimiters: (&'a str, &'a str),
+    pub indent: bool,
+}
+
+impl __Settings<'_> {
+    fn template(&self, content: &str) -> Template {
+        let (open, close) = self.delimiters;
+        Template::new(content)
+            .with_escape(self.escape)
+            .with_delimiters(Delimiters::new(open, close))
+            .with_indent(self.indent)
+    }
+}
+
+#[doc(hidden)]
+pub struct __Sink<'w, W: ?Sized>(RefCell<&'w mut W>);
+
+impl<'w, W: ?Sized> __Sink<'w, W> {
+    pub fn new(out: &'w mut W) -> Self {
+        __Sink(RefCell::new(out))
+    }
+}
+
+#[doc(hidden)]
+pub trait __ViaIoWrite {
+    fn __cans_write(&self, content: &str, settings: __Settings<'_>, context: &dyn Context) -> io::Result<()>;
+}
+
+impl<W: io::Write + ?Sized> __ViaIoWrite for &__Sink<'_, W> {
+    fn __cans_write(&self, content: &str, settings: __Settings<'_>, context: &dyn Context) -> io::Result<()> {
+        settings.template(content).write_to(&mut **self.0.borrow_mut(), context)
+    }
+}
+
+#[doc(hidden)]
+pub trait __ViaFmtWrite {
+    fn __cans_write(&self, content: &str, settings: __Settings<'_>, context: &dyn Context) -> fmt::Result;
+}
+
+impl<W: fmt::Write + ?Sized> __ViaFmtWrite for __Sink<'_, W> {
+    fn __cans_write(&self, content: &str, settings: __Settings<'_>, context: &dyn Context) -> fmt::Result {
+        settings.template(content).render_to(&mut **self.0.borrow_mut(), context)
+    }
+}
+
+#[doc(hidden)]
+pub struct __Layered<'a, C: ?Sized> {
+    values: &'a [(&'a str, __Value<'a>)],
+    context: &'a C,
+}
+
+impl<'a, C: ?Sized> __Layered<'a, C> {
+    pub fn new(values: &'a [(&'a str, __Value<'a>)], context: &'a C) -> Self {
+        __Layered { values, context }
+    }
+}
+
+impl<C: Context + ?Sized> Context for __Layered<'_, C> {
+    fn get(&self, key: &str) -> Option<Value<'_>> {
+        Context::get(self.values, key).or_else(|| self.context.get(key))
+    }
+}
+
+#[doc(hidden)]
+pub fn __render_with<T: AsRef<str> + ?Sized, C: Context + ?Sized>(
+    content: &T,
+    settings: __Settings<'_>,
+    values: &[(&str, __Value<'_>)],
+    context: &C,
+) -> String {
+    settings.template(content.as_ref()).render(&__Layered { values, context })
+}
+
+#[doc(hidden)]
+pub fn __render<C: AsRef<str> + ?Sized>(
+    content: &C,
+    settings: __Settings<'_>,
+    values: &[(&str, __Value<'_>)],
+) -> String {
+    settings.template(content.as_ref()).render(values)
+}
+
+#[doc(hidden)]
+pub fn __try_render_with<T: AsRef<str> + ?Sized, C: Context + ?Sized>(
+    content: &T,
+    settings: __Settings<'_>,
+    values: &[(&str, __Value<'_>)],
+    context: &C,
+) -> Result<String, RenderError> {
+    let template = settings.template(content.as_ref());
+    let result = template.render_strict(&__Layered { values, context });
+    with_unused_arguments(result, &template, values)
+}
+
+#[doc(hidden)]
+pub fn __try_render<C: AsRef<str> + ?Sized>(
+    content: &C,
+    settings: __Settings<'_>,
+    values: &[(&str, __Value<'_>)],
+) -> Result<String, RenderError> {
+    let template = settings.template(content.as_ref());
+    let result = template.render_strict(values);
+    with_unused_arguments(result, &template, values)
+}
+
+/// Adds an issue for every argument that the template never refers to.
+fn with_unused_arguments(
+    result: Result<String, RenderError>,
+    template: &Template,
+    values: &[(&str, __Value<'_>)],
+) -> Result<String, RenderError> {
+    let source = template.source();
+    let delims = template.delimiters().delims();
+    let unused: Vec<Issue> = values
+        .iter()
+        .filter(|(key, _)| !references_key(source.as_bytes(), key.as_bytes(), delims))
+        .map(|(key, _)| Issue::new(IssueKind::UnusedArgument { key: key.to_string() }, 0..0, source))
+        .collect();
+    match result {
+        Ok(output) if unused.is_empty() => Ok(output),
+        Ok(_) => Err(RenderError::new(unused)),
+        Err(error) => Err(RenderError::new(error.issues().iter().cloned().chain(unused).collect())),
+    }
+}
+
+// Compile-time checks emitted by `check_template!`. They share `next_tag` with the renderer,
+// so a template is scanned the same way at compile time and at run time.
+
+// Paths rooted at a loop alias or at a loop variable such as `@index` are bound by the
+// enclosing `{{#each}}`, not by an argument. Partial arguments given as paths are checked like
+// placeholders, and placeholders with a `? "fallback"` or a `default` filter may be left
+// without an argument.
+#[doc(hidden)]
+pub const fn __check_supplied(template: &str, open: &str, close: &str, keys: &[&str]) {
+    let delims = check_delims(open, close);
+    let source = template.as_bytes();
+    let mut aliases: [&[u8]; MAX_LOOP_DEPTH] = [&[]; MAX_LOOP_DEPTH];
+    let mut depth = 0;
+    let mut from = 0;
+    while let Some(tag) = next_tag(source, from, delims) {
+        from = tag.end;
+        if matches!(tag.kind, TagKind::EndEach) && depth > 0 {
+            depth -= 1;
+        }
+        if tag.kind.has_path() && !is_optional(source, &tag, delims) {
+            check_supplied_path(source, tag.path_start, tag.path_end, &aliases, depth, keys, delims);
+        }
+        if matches!(tag.kind, TagKind::Partial) {
+            let mut arg_from = tag.args_start;
+            while let Some(arg) = next_arg(source, arg_from, tag.args_end) {
+                if !arg.literal {
+                    check_supplied_path(source, arg.value_start, arg.value_end, &aliases, depth, keys, delims);
+                }
+                arg_from = arg.end;
+            }
+        }
+        if matches!(tag.kind, TagKind::Each) {
+            if depth < MAX_LOOP_DEPTH {
+                aliases[depth] = source.split_at(tag.name_end).0.split_at(tag.name_start).1;
+            }
+            depth += 1;
+        }
+    }
+}
+
+const fn check_supplied_path(
+    source: &[u8],
+    start: usize,
+    end: usize,
+    aliases: &[&[u8]; MAX_LOOP_DEPTH],
+    depth: usize,
+    keys: &[&str],
+    delims: Delims,
+) {
+    let key = path_root(source, start, end);
+    let mut bound = key[0] == b'@';
+    let mut index = 0;
+    while !bound && index < depth && index < MAX_LOOP_DEPTH {
+        bound = bytes_eq(aliases[index], key);
+        index += 1;
+    }
+    index = 0;
+    while !bound && index < keys.len() {
+        bound = bytes_eq(keys[index].as_bytes(), key);
+        index += 1;
+    }
+    if !bound {
+        let mut buffer = [0; MESSAGE_LEN];
+        let (open, close) = (delims.open.as_bytes(), delims.close.as_bytes());
+        panic!("{}", message(&mut buffer, &[b"no argument supplies the placeholder `", open, key, close, b"`"]));
+    }
+}
+
+#[doc(hidden)]
+pub const fn __check_used(template: &str, open: &str, close: &str, key: &str) {
+    if references_key(template.as_bytes(), key.as_bytes(), check_delims(open, close)) {
+        return;
+    }
+    let mut buffer = [0; MESSAGE_LEN];
+    panic!("{}", message(&mut buffer, &[b"argument `", key.as_bytes(), b"` matches no placeholder in the template"]));
+}
+
+/// Fails the build on empty delimiters, which `Delimiters::new` rejects at run time.
+const fn check_delims<'a>(open: &'a str, close: &'a str) -> Delims<'a> {
+    if open.is_empty() || close.is_empty() {
+        panic!("template delimiters must not be empty");
+    }
+    Delims { open, close }
+}
+
+/// Returns `true` if the placeholder `tag` has a fallback or a `default` filter.
+const fn is_optional(source: &[u8], tag: &TagSpan, delims: Delims) -> bool {
+    if tag.has_default {
+        return true;
+    }
+    let mut from = tag.filters_start;
+    while let Some(filter) = next_filter(source, from, tag.filters_end, delims) {
+        if bytes_eq(source.split_at(filter.name_end).0.split_at(filter.name_start).1, b"default") {
+            return true;
+        }
+        from = filter.end;
+    }
+    false
+}
+
+const MESSAGE_LEN: usize = 256;
+const MAX_LOOP_DEPTH: usize = 32;
+
+/// Joins `parts` into `buffer`, truncating at `MESSAGE_LEN` bytes.
+const fn message<'a>(buffer: &'a mut [u8; MESSAGE_LEN], parts: &[&[u8]]) -> &'a str {
+    let mut len = 0;
+    let mut part = 0;
+    while part < parts.len() {
+        let mut index = 0;
+        while index < parts[part].len() && len < MESSAGE_LEN {
+            buffer[len] = parts[part][index];
+            len += 1;
+            index += 1;
+        }
+        part += 1;
+    }
+    match std::str::from_utf8(buffer.split_at(len).0) {
+        Ok(message) => message,
+        Err(_) => "template check failed",
+    }
+}
@@ -140,6 +140,12 @@ pub struct Country<'a> {
     data: HashMap<&'a str, Details<'a>>, // HashMap to store details of countries keyed by their short names
 }
 
+impl<'a> Default for Country<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> Country<'a> {
     // Creates a new Country instance with some initial example entries.
     ///
@@ -666,7 +672,7 @@ impl<'a> Country<'a> {
     /// # Parameters
     /// - country_code: A string slice containing the 2-letter country code (e.g., "US", "FR").
     /// - key: A string slice specifying which detail to retrieve.
-    ///          Possible values are "flag", "code", "name", and "currency", "capital", "cities".
+    ///   Possible values are "flag", "code", "name", and "currency", "capital", "cities".
     ///
     /// # Returns
     /// Returns a String representation of the requested detail. If the country_code
@@ -836,13 +842,13 @@ impl<'a> Country<'a> {
     /// assert_eq!(non_existing_city_json, r#"{ "name": "", "gmt": [], "latitude": "", "longitude": "", "altitude": "" }"#);
     /// ```
     pub fn city_details(&self, country_code: &'a str, city_name: &'a str) -> String {
-        if let Some(detail) = self.retrieve().get(country_code) {
-            if let Some(city) = detail.cities.iter().find(|&c| c.name == city_name) {
-                return format!(
-                    "{{ \"name\": \"{}\", \"gmt\": {:?}, \"latitude\": \"{}\", \"longitude\": \"{}\", \"altitude\": \"{}\" }}",
-                    city.name, city.gmt, city.latitude, city.longitude, city.altitude
-                );
-            }
+        if let Some(detail) = self.retrieve().get(country_code)
+            && let Some(city) = detail.cities.iter().find(|&c| c.name == city_name)
+        {
+            return format!(
+                "{{ \"name\": \"{}\", \"gmt\": {:?}, \"latitude\": \"{}\", \"longitude\": \"{}\", \"altitude\": \"{}\" }}",
+                city.name, city.gmt, city.latitude, city.longitude, city.altitude
+            );
         }
         String::from("{ \"name\": \"\", \"gmt\": [], \"latitude\": \"\", \"longitude\": \"\", \"altitude\": \"\" }")
         // Return empty if not found
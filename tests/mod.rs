@@ -1,4 +1,5 @@
-use cans::{world::Country, mime::*};
+use cans::{content::*, world::Country, mime::*};
+use cans::{do_html, do_html_file, do_json, do_replace, do_xml, template_loader};
 
 #[cfg(test)]
 mod tests {
@@ -94,4 +95,904 @@ mod tests {
         // Ensure "html" type is removed
         assert!(!mime_types.contains_key("html"), "MIME type for 'html' should be removed.");
     }
+
+    // Tests for the Content module:
+    #[test]
+    fn test_content_macros_escape_for_their_format() {
+        let value = r#"<a href="x">Tom & 'Jerry'</a>"#;
+
+        assert_eq!(do_replace!("{{v}}", v = value), value);
+        assert_eq!(
+            do_html!("{{v}}", v = value),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#x27;Jerry&#x27;&lt;/a&gt;"
+        );
+        assert_eq!(
+            do_xml!("{{v}}", v = value),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;"
+        );
+        assert_eq!(
+            do_json!(r#"{"v": "{{v}}"}"#, v = "line\none \"two\" \\ \u{1}"),
+            r#"{"v": "line\none \"two\" \\ \u0001"}"#
+        );
+    }
+
+    #[test]
+    fn test_raw_values_are_not_escaped() {
+        let head = "<head><title>CANS</title></head>";
+        let result = do_html!("{{HEAD}}<p>{{name}}</p>", HEAD = Raw(head), name = "<b>");
+        assert_eq!(result, "<head><title>CANS</title></head><p>&lt;b&gt;</p>");
+        assert_eq!(do_json!("[{{items}}]", items = Raw(r#""a","b""#)), r#"["a","b"]"#);
+    }
+
+    #[test]
+    fn test_substitution_is_single_pass_and_order_independent() {
+        let template = "{{{a}}} {{b}} {{missing}} {{a}}";
+        let forward = do_replace!(template, a = "{{b}}", b = "B");
+        let backward = do_replace!(template, b = "B", a = "{{b}}");
+
+        assert_eq!(forward, "{{{b}}} B {{missing}} {{b}}");
+        assert_eq!(forward, backward);
+        assert_eq!(do_substitute("{{a}}{{a}}", &[("a", "1"), ("a", "2")]), "11");
+    }
+
+    #[test]
+    fn test_template_renders_many_times_from_one_parse() {
+        let template = Template::new("<li>{{name}}: {{score}}</li>{{unknown}}").with_escape(Escape::Html);
+
+        let first = template.render(&[("name", "Ann & Bo"), ("score", "9")]);
+        let mut second = String::new();
+        template.render_to(&mut second, &vec![("name", "Cy".to_string()), ("score", 7.to_string())]).unwrap();
+
+        assert_eq!(first, "<li>Ann &amp; Bo: 9</li>{{unknown}}");
+        assert_eq!(second, "<li>Cy: 7</li>{{unknown}}");
+        assert_eq!(template.source(), "<li>{{name}}: {{score}}</li>{{unknown}}");
+    }
+
+    #[test]
+    fn test_macros_accept_display_and_template_values() {
+        struct Celsius(f32);
+
+        impl std::fmt::Display for Celsius {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}°C <warm>", self.0)
+            }
+        }
+
+        let name = String::from("Khartoum");
+        let result = do_html!("{{city}} {{temp}} {{ok}}", city = name, temp = Celsius(41.5), ok = true);
+        assert_eq!(result, "Khartoum 41.5°C &lt;warm&gt; true");
+    }
+
+    #[test]
+    fn test_render_with_runtime_contexts() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let template = "{{greeting}}, {{user-name}}!";
+
+        let hash: HashMap<String, String> = [("greeting", "Hello"), ("user-name", "Sara")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let btree: BTreeMap<&str, &str> = [("greeting", "Hello"), ("user-name", "Sara")].into_iter().collect();
+        let pairs = vec![("greeting".to_string(), "Hello"), ("user-name".to_string(), "Sara")];
+        let closure = from_fn(|key: &str| match key {
+            "greeting" => Some("Hello"),
+            "user-name" => Some("Sara"),
+            _ => None,
+        });
+
+        assert_eq!(render_with(template, &hash), "Hello, Sara!");
+        assert_eq!(render_with(template, &btree), "Hello, Sara!");
+        assert_eq!(render_with(template, &pairs), "Hello, Sara!");
+        assert_eq!(render_with(template, &closure), "Hello, Sara!");
+        assert_eq!(render_with(template, &[("greeting", "Hi")]), "Hi, {{user-name}}!");
+    }
+
+    #[test]
+    fn test_derive_context_exposes_fields() {
+        #[derive(cans::Context)]
+        struct Meta {
+            description: &'static str,
+        }
+
+        #[derive(cans::Context)]
+        struct Page<'a, T: std::fmt::Display = u32> {
+            #[cans(rename = "page_title")]
+            title: String,
+            author: &'a str,
+            #[cans(skip)]
+            #[allow(dead_code)]
+            secret: T,
+            visits: u32,
+            #[cans(flatten)]
+            meta: Meta,
+        }
+
+        let page = Page {
+            title: "Home".to_string(),
+            author: "<Sara>",
+            secret: 42,
+            visits: 7,
+            meta: Meta { description: "CANS & Rust" },
+        };
+
+        let template = "{{page_title}} by {{author}} ({{visits}}) {{description}} {{secret}} {{title}}";
+        assert_eq!(
+            Template::new(template).render(&page),
+            "Home by <Sara> (7) CANS & Rust {{secret}} {{title}}"
+        );
+        assert_eq!(
+            do_html!("<h1>{{page_title}}</h1><p>{{author}}</p>", ..page),
+            "<h1>Home</h1><p>&lt;Sara&gt;</p>"
+        );
+        assert_eq!(
+            do_html!("<h1>{{page_title}}</h1><p>{{author}}</p>", author = "Ahmed", ..page),
+            "<h1>Home</h1><p>Ahmed</p>"
+        );
+    }
+
+    #[test]
+    fn test_derive_context_for_tuple_structs() {
+        #[derive(cans::Context)]
+        struct Pair(&'static str, i32);
+
+        assert_eq!(render_with("{{0}} = {{1}}", &Pair("x", 1)), "x = 1");
+    }
+
+    #[test]
+    fn test_dotted_paths_walk_nested_contexts() {
+        #[derive(cans::Context)]
+        struct Item {
+            name: String,
+        }
+
+        #[derive(cans::Context)]
+        struct Order {
+            items: Vec<Item>,
+        }
+
+        let order = Order {
+            items: vec![Item { name: "Tea".into() }, Item { name: "<Cake>".into() }],
+        };
+        let mut address = std::collections::HashMap::new();
+        address.insert("city", "Khartoum");
+
+        assert_eq!(do_html!("{{order.items.1.name}}", order = order), "&lt;Cake&gt;");
+        assert_eq!(render_with("{{address.city}}", &[("address", &address)]), "Khartoum");
+        assert_eq!(render_with("{{order.items.0.name}}", &[("order.items.0.name", "flat")]), "flat");
+        assert_eq!(do_replace!("{{order.items.5.name}}", ..[("order", &order)]), "{{order.items.5.name}}");
+
+        let error = Template::new("{{order.items.5.name}} {{order.total}}")
+            .render_strict(&[("order", &order)])
+            .unwrap_err();
+        let issues = error.issues();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].span, 0..22);
+        assert_eq!(
+            issues[0].kind,
+            IssueKind::MissingSegment {
+                tag: "{{order.items.5.name}}".into(),
+                path: "order.items".into(),
+                segment: "5".into(),
+            }
+        );
+        assert_eq!(issues[1].span, 23..38);
+    }
+
+    #[test]
+    fn test_conditional_sections_follow_truthiness() {
+        let template = Template::new("{{#if flag}}yes{{else}}no{{/if}}|{{#unless flag}}empty{{/unless}}");
+        let falsy: [&dyn TemplateValue; 7] = [&"", &false, &0, &0.0, &None::<&str>, &Vec::<u8>::new(), &[0u8; 0]];
+        for value in falsy {
+            assert_eq!(template.render(&[("flag", value)]), "no|empty");
+        }
+        let truthy: [&dyn TemplateValue; 5] = [&"0", &true, &-1, &Some("x"), &vec![""]];
+        for value in truthy {
+            assert_eq!(template.render(&[("flag", value)]), "yes|");
+        }
+        assert_eq!(template.render(&[("other", "x")]), "no|empty");
+
+        let nested = Template::new("{{#if a}}A{{#unless b}}!b{{else}}b{{/unless}}{{/if}}");
+        assert_eq!(nested.render(&[("a", true), ("b", false)]), "A!b");
+        assert_eq!(nested.render(&[("a", false), ("b", false)]), "");
+
+        #[derive(cans::Context)]
+        struct User {
+            admin: bool,
+        }
+        assert_eq!(do_replace!("{{#if user.admin}}admin{{/if}}", user = User { admin: true }), "admin");
+    }
+
+    #[test]
+    fn test_malformed_sections_are_literal_and_reported() {
+        let template = Template::new("{{/if}}{{#if a}}x{{else}}y{{else}}z");
+        assert_eq!(template.render(&[("a", true)]), "{{/if}}{{#if a}}x{{else}}y{{else}}z");
+
+        let error = template.render_strict(&[("a", true)]).unwrap_err();
+        let kinds: Vec<_> = error.issues().iter().map(|issue| (issue.span.clone(), issue.kind.clone())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (0..7, IssueKind::UnexpectedTag { tag: "{{/if}}".into() }),
+                (7..16, IssueKind::UnclosedSection { tag: "{{#if a}}".into() }),
+                (26..34, IssueKind::UnexpectedTag { tag: "{{else}}".into() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_each_blocks_bind_items_and_loop_variables() {
+        #[derive(cans::Context)]
+        struct Group {
+            name: &'static str,
+            members: Vec<&'static str>,
+        }
+
+        let groups = vec![
+            Group { name: "A", members: vec!["x", "y"] },
+            Group { name: "B", members: vec![] },
+        ];
+        let template = "{{#each groups as group}}{{group.name}}:\
+            {{#each group.members as member}}{{member}}{{#if @last}}.{{else}},{{/if}}{{else}}none{{/each}}\
+            {{#unless @last}} {{/unless}}{{/each}}";
+        assert_eq!(do_replace!(template, groups = groups), "A:x,y. B:none");
+
+        // The enclosing context stays visible and the alias shadows it.
+        let template = "{{#each items as item}}{{prefix}}{{item}}{{/each}}|{{item}}";
+        assert_eq!(
+            render_with(template, &[("items", vec!["1", "2"]), ("prefix", vec!["-"]), ("item", vec!["outer"])]),
+            "-1-2|outer"
+        );
+
+        // A missing list renders nothing and is reported in strict mode.
+        let error = Template::new("{{#each rows as row}}{{row}}{{/each}}").render_strict(&[("x", 1)]).unwrap_err();
+        assert_eq!(error.issues()[0].kind, IssueKind::Unresolved { tag: "{{#each rows as row}}".into() });
+        assert_eq!(Template::new("{{#each rows as row}}{{row}}{{/each}}").render(&[("rows", None::<u8>)]), "");
+    }
+
+    #[test]
+    fn test_registry_renders_partials_with_arguments() {
+        #[derive(cans::Context)]
+        struct Page {
+            alpinejs: Raw<String>,
+            page_title: &'static str,
+            posts: Vec<&'static str>,
+        }
+
+        let mut registry = TemplateRegistry::new().with_escape(Escape::Html);
+        registry
+            .register("head", "<head>{{alpinejs}}<title>{{page_title}}</title></head>")
+            .register("item", "<li>{{index}}. {{text}} ({{label}})</li>")
+            .register(
+                "home",
+                "{{> head}}<ul>{{#each posts as post}}{{> item text=post index=@index label=\"a & b\"}}{{/each}}</ul>",
+            );
+
+        let page = Page { alpinejs: Raw(alpine("latest")), page_title: "Home", posts: vec!["<One>", "Two"] };
+        assert_eq!(
+            registry.render("home", &page).unwrap(),
+            format!(
+                "<head>{}<title>Home</title></head><ul><li>0. &lt;One&gt; (a & b)</li><li>1. Two (a & b)</li></ul>",
+                alpine("latest")
+            )
+        );
+        assert!(registry.contains("head"));
+        assert_eq!(registry.get("head").unwrap().escape(), Escape::Html);
+
+        // Standalone templates have no registry to resolve partials from.
+        let template = Template::new("{{> head}}");
+        assert_eq!(template.render(&page), "{{> head}}");
+        assert!(matches!(
+            template.render_strict(&page).unwrap_err().issues()[0].kind,
+            IssueKind::UnknownPartial { .. }
+        ));
+    }
+
+    #[test]
+    fn test_registry_limits_partial_depth() {
+        let mut registry = TemplateRegistry::new().with_max_depth(2);
+        registry.register("a", "a{{> b}}").register("b", "b{{> c}}").register("c", "c{{> d}}").register("d", "d");
+
+        assert_eq!(registry.render("a", &[("x", "")]).unwrap(), "abc{{> d}}");
+        let error = registry.render_strict("a", &[("x", "")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "template could not be rendered:\n- `{{> d}}` in `c` (line 1, column 2, bytes 1..8): partials are nested more than 2 levels deep"
+        );
+        assert_eq!(
+            registry.render_strict("z", &[("x", "")]).unwrap_err().to_string(),
+            "template could not be rendered:\n- no template named `z` is registered"
+        );
+    }
+
+    #[test]
+    fn test_layouts_override_blocks() {
+        let mut registry = TemplateRegistry::new().with_escape(Escape::Html);
+        registry
+            .register("base", "<title>{{#block title}}Site{{/block}}</title>{{#block body}}empty{{/block}}")
+            .register("post", "{{extends \"base\"}}ignored{{#block title}}{{title}} - {{super()}}{{/block}}")
+            .register("loop", "{{extends \"loop\"}}")
+            .register("orphan", "{{extends \"missing\"}}{{#block body}}x{{/block}}");
+
+        assert_eq!(
+            registry.render("post", &[("title", "<Hi>")]).unwrap(),
+            "<title>&lt;Hi&gt; - Site</title>empty"
+        );
+        assert_eq!(registry.render("base", &[("title", "")]).unwrap(), "<title>Site</title>empty");
+
+        let error = registry.render_strict("loop", &[("x", "")]).unwrap_err();
+        assert!(matches!(error.issues()[0].kind, IssueKind::PartialCycle { .. }));
+
+        // An unresolved layout renders the template on its own.
+        assert_eq!(registry.render("orphan", &[("x", "")]).unwrap(), "{{extends \"missing\"}}x");
+        let error = Template::new("{{super()}}{{#block a}}{{/block}}{{extends \"b\"}}").render_strict(&[("x", "")]).unwrap_err();
+        let kinds: Vec<_> = error.issues().iter().map(|issue| issue.span.clone()).collect();
+        assert_eq!(kinds, [0..11, 33..48]);
+    }
+
+    #[test]
+    fn test_placeholder_filters() {
+        let template = Template::new(
+            "{{name|trim|title}} {{price|round:2}} {{bio|truncate:5}} {{tags|join:\" | \"|upper}} {{note|default:\"<none>\"}} {{q|urlencode}}",
+        )
+        .with_escape(Escape::Html);
+
+        #[derive(cans::Context)]
+        struct Item {
+            name: &'static str,
+            price: f64,
+            bio: &'static str,
+            tags: Vec<&'static str>,
+            note: Option<&'static str>,
+            q: &'static str,
+        }
+        let item = Item { name: "  fish & chips ", price: 12.345, bio: "Fresh daily", tags: vec!["a<b", "c"], note: None, q: "a b&c" };
+        assert_eq!(template.render(&item), "Fish &amp; Chips 12.35 Fresh... A&lt;B | C <none> a%20b%26c");
+        assert_eq!(do_html!("{{html|raw}}{{html}}", html = "<b>"), "<b>&lt;b&gt;");
+        assert_eq!(do_replace!("{{name|escape_html}}", name = "<b>"), "&lt;b&gt;");
+        assert_eq!(do_html!("{{x}}: {{missing|default:\"n/a\"}}", x = 1), "1: n/a");
+
+        // Integers are rounded exactly, even above the precision of an f64.
+        let round = Template::new("{{a|round}} {{a|round:2}} {{b|round}} {{c|round:1}}");
+        let context = [("a", Value::Int(12345678901234567891)), ("b", Value::Int(i128::MIN)), ("c", Value::from("98765432109876543210"))];
+        assert_eq!(round.render(&context), "12345678901234567891 12345678901234567891.00 -170141183460469231731687303715884105728 98765432109876543210.0");
+        assert_eq!(do_replace!("{{a|round:1}}", a = 12345678901234567891u64), "12345678901234567891.0");
+
+        let error = Template::new("{{a|shout}} {{a|round}}").render_strict(&[("a", "x")]).unwrap_err();
+        assert_eq!(
+            error.issues().iter().map(|issue| issue.kind.clone()).collect::<Vec<_>>(),
+            [
+                IssueKind::UnknownFilter { tag: "{{a|shout}}".into(), name: "shout".into() },
+                IssueKind::FilterFailed { tag: "{{a|round}}".into(), filter: "round".into(), message: "`x` is not a number".into() },
+            ]
+        );
+
+        let mut registry = TemplateRegistry::new();
+        registry
+            .register_filter("shout", |value, args| Ok(Value::from(format!("{value}{}", args.first().unwrap_or(&"!")))))
+            .register_filter("upper", |value, _| Ok(value))
+            .register("page", "{{a|shout}} {{a|shout:\"?\"|upper}}");
+        assert_eq!(registry.render("page", &[("a", "hi")]).unwrap(), "hi! hi?");
+    }
+
+    #[test]
+    fn test_placeholder_fallbacks_and_required_markers() {
+        let template = Template::new("<h1>{{title!}}</h1><h2>{{subtitle ? \"Welcome & enjoy\"}}</h2>").with_escape(Escape::Html);
+        assert_eq!(template.render(&[("title", "<Home>")]), "<h1>&lt;Home&gt;</h1><h2>Welcome & enjoy</h2>");
+        assert_eq!(
+            template.render(&[("title", "Home"), ("subtitle", "Hi & bye")]),
+            "<h1>Home</h1><h2>Hi &amp; bye</h2>"
+        );
+        assert_eq!(template.render_strict(&[("title", "Home")]).unwrap(), "<h1>Home</h1><h2>Welcome & enjoy</h2>");
+
+        // A null value counts as missing for both markers.
+        let none: Option<&str> = None;
+        assert_eq!(do_html!("{{title!}}|{{subtitle ? \"-\"}}", title = none, subtitle = none), "{{title!}}|-");
+        let error = template.render_strict(&[("subtitle", "x")]).unwrap_err();
+        assert_eq!(error.issues()[0].kind, IssueKind::MissingRequired { tag: "{{title!}}".into() });
+
+        // Filters apply to supplied values only, and malformed markers are literal text.
+        assert_eq!(do_replace!("{{name|upper ? \"anon\"}} {{x!!}} {{y ? anon}}", name = "amal"), "AMAL {{x!!}} {{y ? anon}}");
+        assert_eq!(Template::new("{{name|upper?\"anon\"}}").render(&[("x", "")]), "anon");
+    }
+
+    #[test]
+    fn test_try_macros_report_every_problem() {
+        assert_eq!(try_do_html!("<b>{{name}}</b>", name = "A & B").unwrap(), "<b>A &amp; B</b>");
+
+        #[derive(cans::Context)]
+        struct Page {
+            title: &'static str,
+        }
+        let page = Page { title: "Home" };
+        let template = "<title>{{title}}</title>\n<p>{{ body }}</p>\n<p>{{ünïcode}} {{#if x}}</p>".to_string();
+        let error = try_do_html!(template, lang = "en", ..page).unwrap_err();
+        let found: Vec<_> = error.issues().iter().map(|issue| (issue.kind.clone(), issue.line, issue.column)).collect();
+        assert_eq!(
+            found,
+            [
+                (IssueKind::MalformedTag { tag: "{{ body }}".into() }, 2, 4),
+                (IssueKind::MalformedTag { tag: "{{ünïcode}}".into() }, 3, 4),
+                (IssueKind::UnclosedSection { tag: "{{#if x}}".into() }, 3, 16),
+                (IssueKind::UnusedArgument { key: "lang".into() }, 1, 1),
+            ]
+        );
+
+        // Unclosed tags run to the end of the line.
+        let error = try_do_xml!("<a>{{name</a>\n<b>{{other}}</b>".to_string(), other = 1).unwrap_err();
+        assert_eq!(error.issues()[0].kind, IssueKind::MalformedTag { tag: "{{name</a>".into() });
+        assert_eq!(error.issues().len(), 1);
+    }
+
+    #[test]
+    fn test_placeholders_lists_references_in_order() {
+        let template = Template::new(
+            "{{#unless hidden}}{{> card title=post.title note=\"hi\"}}{{/unless}}\n  {{price|round:2|raw}} {{ skipped }} {{/each}}",
+        );
+        let found = template.placeholders();
+        assert_eq!(found, placeholders(template.source()));
+        assert_eq!(
+            found.iter().map(|found| (found.kind.clone(), found.name.as_str(), found.line, found.column)).collect::<Vec<_>>(),
+            [
+                (PlaceholderKind::Section { negated: true }, "hidden", 1, 1),
+                (PlaceholderKind::Partial { args: vec![("title".into(), "post.title".into())] }, "card", 1, 19),
+                (
+                    PlaceholderKind::Value { required: false, default: None, filters: vec!["round".into(), "raw".into()] },
+                    "price",
+                    2,
+                    3
+                ),
+            ]
+        );
+        assert_eq!(&template.source()[found[2].span.clone()], "{{price|round:2|raw}}");
+    }
+
+    #[test]
+    fn test_custom_delimiters_and_escaped_tags() {
+        let brackets = Delimiters::new("[[", "]]");
+        let template = Template::new("<p v-if=\"ok\">{{ message }}</p>[[#if ok]][[name|upper]][[/if]] [[\"[[\"]]name]]")
+            .with_delimiters(brackets.clone());
+        assert_eq!(template.delimiters(), &brackets);
+        let context = [("ok", Value::Bool(true)), ("name", Value::from("ada"))];
+        assert_eq!(template.render_strict(&context).unwrap(), "<p v-if=\"ok\">{{ message }}</p>ADA [[name]]");
+        assert_eq!(template.placeholders().iter().map(|found| found.name.as_str()).collect::<Vec<_>>(), ["ok", "name"]);
+
+        let mut registry = TemplateRegistry::new().with_delimiters(Delimiters::new("<%=", "%>"));
+        registry.register("item", "<li><%=label%></li>").register("list", "<ul><%=#each items as label%><%=> item%><%=/each%></ul>");
+        let items = [("items", Value::List(vec![Value::from("a"), Value::from("b")]))];
+        assert_eq!(registry.render_strict("list", &items).unwrap(), "<ul><li>a</li><li>b</li></ul>");
+
+        assert_eq!(do_html!(@delimiters("[[", "]]"), "{{x}} [[x]]", x = "<i>"), "{{x}} &lt;i&gt;");
+        assert_eq!(do_replace!(r#"{{"{{"}}x}} {{x}}"#, x = 1), "{{x}} 1");
+
+        // Literal tags may hold either delimiter, and the compile-time check skips them.
+        assert_eq!(do_html!(r#"{{"{{x}}"}} {{- "}}" -}} {{x}}"#, x = "<i>"), "{{x}}}}&lt;i&gt;");
+        let literal = Template::new(r#"{{"a"}}{{"{{"}}b}} {{"c}}"#);
+        assert_eq!(literal.render(&[("b", "x")]), r#"a{{b}} {{"c}}"#);
+        assert!(literal.placeholders().is_empty());
+
+        // Backslashes before a tag are written as they are.
+        assert_eq!(do_replace!(r"C:\{{dir}} C:\\{{dir}}", dir = "x"), r"C:\x C:\\x");
+        assert_eq!(do_json!(r#"{"path":"C:\\{{dir}}"}"#, dir = "Users"), r#"{"path":"C:\\Users"}"#);
+        assert_eq!(Template::new(r"C:\{{dir}}").render(&[("dir", "x")]), r"C:\x");
+        let error = cans::try_do_replace!(@delimiters("[[", "]]"), "[[x]] [[ y ]]".to_string(), x = 1).unwrap_err();
+        assert_eq!(error.issues()[0].kind, IssueKind::MalformedTag { tag: "[[ y ]]".into() });
+    }
+
+    #[test]
+    fn test_streaming_into_io_and_fmt_sinks() {
+        use std::io::{self, Write};
+
+        let rows = vec![1, 2, 3];
+        let template = Template::new("<table>{{#each rows as row}}<tr>{{row}}</tr>{{/each}}</table>");
+        let mut file = io::BufWriter::new(Vec::new());
+        template.write_to(&mut file, &[("rows", &rows)]).unwrap();
+        do_forloop_write(&mut file, &rows, "<ol>", "<li>", "</li>", "</ol>").unwrap();
+        do_html!(@write(&mut file), "<p>{{note}}</p>", note = "<done>").unwrap();
+        file.flush().unwrap();
+        assert_eq!(
+            String::from_utf8(file.into_inner().unwrap()).unwrap(),
+            "<table><tr>1</tr><tr>2</tr><tr>3</tr></table><ol><li>1</li><li>2</li><li>3</li></ol><p>&lt;done&gt;</p>"
+        );
+
+        let mut page = String::new();
+        template.render_to(&mut page, &[("rows", &rows)]).unwrap();
+        do_forloop_to(&mut page, &rows, "", "", ",", "").unwrap();
+        assert_eq!(page, "<table><tr>1</tr><tr>2</tr><tr>3</tr></table>1,2,3,");
+
+        // The first I/O error stops rendering and is returned as it is.
+        struct Full(usize);
+        impl Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let error = template.write_to(&mut Full(10), &[("rows", &rows)]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+
+        let mut registry = TemplateRegistry::new();
+        registry.register("page", "{{> head}}body").register("head", "<head>{{title}}</head>");
+        let mut socket: Vec<u8> = Vec::new();
+        registry.write_to("page", &mut socket, &[("title", "T")]).unwrap().unwrap();
+        assert_eq!(socket, b"<head>T</head>body");
+        assert!(registry.write_to("missing", &mut socket, &[("title", "T")]).is_none());
+    }
+
+    #[test]
+    fn test_template_files_and_hot_reload() {
+        use std::fs;
+        use std::time::{Duration, SystemTime};
+
+        let card = do_html_file!("tests/templates/partials/card.html", title = "<Hi>");
+        assert_eq!(card, "<article>&lt;Hi&gt;</article>");
+
+        #[derive(cans::Context)]
+        struct Post {
+            title: &'static str,
+        }
+        #[derive(cans::Context)]
+        struct Home {
+            title: &'static str,
+            posts: Vec<Post>,
+        }
+        let home = Home { title: "Home", posts: vec![Post { title: "A & B" }] };
+        let embedded = TemplateLoader::from_embedded(cans::embed_templates!("tests/templates"));
+        let loaded = template_loader!("tests/templates").unwrap();
+        for loader in [&embedded, &loaded] {
+            assert_eq!(loader.render_strict("home.html", &home).unwrap(), "<h1>Home</h1><article>A &amp; B</article>");
+        }
+
+        let dir = std::env::temp_dir().join(format!("cans-loader-{}", std::process::id()));
+        fs::create_dir_all(dir.join("parts")).unwrap();
+        fs::write(dir.join("page.html"), "<p>{{> parts/name.txt}}</p>").unwrap();
+        fs::write(dir.join("parts/name.txt"), "{{name}}").unwrap();
+        fs::write(dir.join(".hidden"), "skipped").unwrap();
+        let loader = TemplateLoader::new(&dir).unwrap().with_escape(Escape::None).with_hot_reload(true);
+        assert_eq!(loader.render("page.html", &[("name", "<b>")]).unwrap(), "<p><b></p>");
+        assert!(!loader.contains(".hidden"));
+
+        // Changed files are picked up by their modification time, removed files are dropped.
+        fs::write(dir.join("parts/name.txt"), "[{{name}}]").unwrap();
+        let file = fs::File::options().write(true).open(dir.join("parts/name.txt")).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+        fs::write(dir.join("new.html"), "new").unwrap();
+        assert_eq!(loader.render("page.html", &[("name", "<b>")]).unwrap(), "<p>[<b>]</p>");
+        assert_eq!(loader.render("new.html", &[("name", "")]).unwrap(), "new");
+        fs::remove_file(dir.join("new.html")).unwrap();
+        assert!(!loader.contains("new.html"));
+        assert!(!loader.reload().unwrap());
+
+        let frozen = TemplateLoader::new(&dir).unwrap().with_hot_reload(false);
+        fs::write(dir.join("page.html"), "changed").unwrap();
+        assert!(frozen.render("page.html", &[("name", "x")]).unwrap().starts_with("<p>"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_comments_and_whitespace_control() {
+        let page = do_html!("<p>{{! design note: keep it short }}{{name}}</p>{{-! trailing note -}}\n", name = "Ada");
+        assert_eq!(page, "<p>Ada</p>");
+
+        let template = Template::new(
+            "<pre>\n  {{- #each rows as row -}}\n    {{row}}\n  {{- /each -}}\n</pre>\n<b>{{- title -}}</b> {{title-}}",
+        );
+        let context = [("rows", Value::List(vec![Value::Int(1), Value::Int(2)])), ("title", Value::from("T")), ("title-", Value::from("dash"))];
+        assert_eq!(template.render_strict(&context).unwrap(), "<pre>12</pre>\n<b>T</b> dash");
+
+        let json = do_json!("{\n  \"name\": \"{{name}}\",\n  {{- ! the id is optional }}\n  \"id\": {{id}}\n}", name = "x", id = 7);
+        assert_eq!(json, "{\n  \"name\": \"x\",\n  \"id\": 7\n}");
+
+        // Trim markers need a space next to the `-`, and spaces before `}}` need a `{{- `.
+        let template = Template::new("{{- name }} {{ name -}} {{-name}} {{ name }}");
+        let error = template.render_strict(&[("name", "n"), ("-name", "m")]).unwrap_err();
+        assert_eq!(error.issues()[0].kind, IssueKind::MalformedTag { tag: "{{ name }}".into() });
+        assert_eq!(error.issues().len(), 1);
+        assert_eq!(template.render(&[("name", "n"), ("-name", "m")]), "n nm {{ name }}");
+        assert!(placeholders("{{! {{ignored}} }}{{-! x -}}").is_empty());
+    }
+
+    #[test]
+    fn test_indentation_aware_substitution() {
+        let steps = "- run: cargo build\n- run: cargo test";
+        let yaml = do_replace!(@indent, "jobs:\n  test:\n    steps:\n      {{steps}}\n", steps = steps);
+        assert_eq!(yaml, "jobs:\n  test:\n    steps:\n      - run: cargo build\n      - run: cargo test\n");
+
+        // Without the mode only the first line is indented.
+        let flat = do_replace!("    {{steps}}", steps = steps);
+        assert_eq!(flat, "    - run: cargo build\n- run: cargo test");
+
+        // Tabs are kept, empty lines stay empty and text before the placeholder counts as columns.
+        let body = "if ready:\n    start()\n\nfinish()";
+        let python = Template::new("def main():\n\t{{body}}\nx = {{body}}").with_indent(true);
+        assert_eq!(
+            python.render(&[("body", body)]),
+            "def main():\n\tif ready:\n\t    start()\n\n\tfinish()\nx = if ready:\n        start()\n\n    finish()"
+        );
+
+        // Values inside loops and partials line up with the output column.
+        let mut registry = TemplateRegistry::new().with_indent(true);
+        registry
+            .register("impl", "impl {{name}} {\n    {{> method}}\n}")
+            .register("method", "fn new() -> Self {\n        {{body}}\n    }");
+        let rust = registry.render("impl", &[("name", "Point"), ("body", "Point {\n    x: 0,\n}")]).unwrap();
+        assert_eq!(rust, "impl Point {\n    fn new() -> Self {\n        Point {\n            x: 0,\n        }\n    }\n}");
+
+        let list = do_html!(@indent, "<ul>\n  {{#each items as item}}<li>{{item}}</li>{{/each}}\n</ul>", items = ["a\nb", "c"]);
+        assert_eq!(list, "<ul>\n  <li>a\n      b</li><li>c</li>\n</ul>");
+        assert!(cans::try_do_replace!(@indent, "  {{x}}".to_string(), x = "1\n2").unwrap() == "  1\n  2");
+    }
+
+    #[test]
+    fn test_forloop_builder() {
+        let items = ["a", "b", "c"];
+        assert_eq!(ForLoop::new(&items).separator(", ").render(), "a, b, c");
+        assert_eq!(
+            ForLoop::new(&items).before_items("[").before_item("<").after_item(">").separator(";").after_items("]").render(),
+            "[<a>;<b>;<c>]"
+        );
+        let classes = ForLoop::new(&items)
+            .map(|index, item| match (index == 0, index + 1 == items.len()) {
+                (true, _) => format!("first:{item}"),
+                (_, true) => format!("last:{item}"),
+                _ => item.to_string(),
+            })
+            .separator(" ")
+            .render();
+        assert_eq!(classes, "first:a b last:c");
+
+        // The fallback replaces the text around the items too; without one, it is still written.
+        let none: [i32; 0] = [];
+        assert_eq!(ForLoop::new(&none).before_items("<ul>").after_items("</ul>").empty("-").render(), "-");
+        assert_eq!(ForLoop::new(&none).before_items("<ul>").after_items("</ul>").render(), "<ul></ul>");
+        assert_eq!(ForLoop::new(&[7]).separator(",").empty("-").render(), "7");
+
+        let mut page = String::from(">");
+        ForLoop::new(&[1, 2]).separator("+").render_to(&mut page).unwrap();
+        let mut bytes = Vec::new();
+        ForLoop::from_fn(&[(1, 2)], |_, (a, b)| format!("{a}/{b}")).write_to(&mut bytes).unwrap();
+        assert_eq!((page.as_str(), bytes.as_slice()), (">1+2", &b"1/2"[..]));
+    }
+
+    #[test]
+    fn test_json_arrays_and_objects() {
+        assert_eq!(do_json_forloop(&["a\"b", "c\\d\n"]), r#"["a\"b","c\\d\n"]"#);
+        assert_eq!(do_json_forloop(&[-1i64, 0, 42]), "[-1,0,42]");
+        assert_eq!(do_json_forloop(&[0.5, f64::INFINITY, f64::NAN]), "[0.5,null,null]");
+        assert_eq!(do_json_forloop::<&str>(&[]), "[]");
+        assert_eq!(do_json_forloop(&['"', '\u{1}']), r#"["\"","\u0001"]"#);
+
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("k\"ey".to_string(), vec![Some(true), None]);
+        let object = JsonObject::new()
+            .field("map", &map)
+            .field("empty", JsonObject::new())
+            .field("list", JsonArray::new().push(1).push("two").push(JsonArray::new().push(3.25)));
+        assert_eq!(
+            object.to_json(),
+            r#"{"map":{"k\"ey":[true,null]},"empty":{},"list":[1,"two",[3.25]]}"#
+        );
+        assert_eq!(object.to_string(), object.clone().into_string());
+        assert!(JsonObject::new().is_empty() && !JsonArray::new().push(0).is_empty());
+
+        // Builders are inserted into JSON templates as JSON, giving the same text as building the whole document.
+        let posts: JsonArray = [("Hello \"you\"", 3), ("W\\orld", 0)]
+            .iter()
+            .map(|(title, likes)| JsonObject::new().field("title", *title).field("likes", *likes))
+            .collect();
+        let json = do_json!(r#"{"posts":{{posts}},"note":"{{note}}"}"#, posts = posts.clone(), note = "\"hi\"");
+        assert_eq!(json, JsonObject::new().field("posts", posts.clone()).field("note", "\"hi\"").to_json());
+        assert_eq!(json, r#"{"posts":[{"title":"Hello \"you\"","likes":3},{"title":"W\\orld","likes":0}],"note":"\"hi\""}"#);
+        let user = JsonObject::new().field("name", "<\"x\">");
+        assert_eq!(Template::new("{{user}}").with_escape(Escape::Json).render(&[("user", &user)]), r#"{"name":"<\"x\">"}"#);
+        assert_eq!(do_json!("{{user|raw}}", user = &user), r#"{"name":"<\"x\">"}"#);
+
+        // Plain strings, such as the result of do_json_forloop, and filtered builders are escaped like any other text.
+        assert_eq!(do_json!(r#"["{{tags}}"]"#, tags = do_json_forloop(&["a"])), r#"["[\"a\"]"]"#);
+        assert_eq!(do_json!(r#""{{user|upper}}""#, user = &user), r#""{\"NAME\":\"<\\\"X\\\">\"}""#);
+
+        // Other formats escape builders like any other text.
+        let payload = "</script><img src=x onerror=alert(1)>";
+        assert_eq!(
+            do_html!("<p>{{d}}</p>", d = JsonArray::new().push(payload)),
+            "<p>[&quot;&lt;/script&gt;&lt;img src=x onerror=alert(1)&gt;&quot;]</p>"
+        );
+        assert_eq!(
+            do_html!("<div data-user=\"{{u}}\"></div>", u = JsonObject::new().field("n", "\"><b>")),
+            "<div data-user=\"{&quot;n&quot;:&quot;\\&quot;&gt;&lt;b&gt;&quot;}\"></div>"
+        );
+        assert_eq!(do_xml!("<d>{{d}}</d>", d = JsonArray::new().push("<x/>")), "<d>[&quot;&lt;x/&gt;&quot;]</d>");
+    }
+
+    #[test]
+    fn test_typed_values_and_number_formatting() {
+        use std::collections::{BTreeSet, VecDeque};
+        use std::rc::Rc;
+
+        let none: Option<i32> = None;
+        let set: BTreeSet<_> = [3, 1, 2].into_iter().collect();
+        let queue: VecDeque<_> = ["b", "a"].into_iter().collect();
+        assert_eq!(
+            do_replace!("[{{none}}|{{some}}|{{flag}}|{{set}}|{{queue}}|{{shared}}]", none = none, some = Some(2.5), flag = false, set = set, queue = queue, shared = Rc::new("rc")),
+            "[|2.5|false|1, 2, 3|b, a|rc]"
+        );
+        assert_eq!(do_html!("{{x}}", x = Join(["<a>", "b"], " & ")), "&lt;a&gt; &amp; b");
+        assert_eq!(Join(Vec::<i32>::new(), "-").to_string(), "");
+
+        assert_eq!(Number::new(1234567).to_string(), "1234567");
+        assert_eq!(Number::new(1234567).thousands(',').to_string(), "1,234,567");
+        assert_eq!(Number::new(123).thousands(',').to_string(), "123");
+        assert_eq!(Number::new(-1234.567).precision(1).thousands(',').to_string(), "-1,234.6");
+        assert_eq!(Number::new(12).precision(2).to_string(), "12.00");
+        assert_eq!(Number::new(u64::MAX).thousands('_').to_string(), "18_446_744_073_709_551_615");
+        assert_eq!(Number::new("2500.5").thousands(',').to_string(), "2,500.5");
+        assert_eq!(Number::new(f64::NAN).thousands(',').to_string(), "NaN");
+        assert_eq!(Number::new("n/a").precision(2).to_string(), "n/a");
+
+        let template = Template::new("{{a|number}} {{a|number:2}} {{a|number:0,\",\"}} {{b|number:\"\",\".\",\",\"}}");
+        assert_eq!(template.render(&[("a", Value::Float(98765.4321)), ("b", Value::Float(1234.5))]), "98765.4321 98765.43 98,765 1.234,5");
+        let error = Template::new("{{a|number:x}}").render_strict(&[("a", 1)]).unwrap_err();
+        assert!(matches!(error.issues()[0].kind, IssueKind::FilterFailed { .. }), "{error}");
+
+        struct Celsius(f64);
+        impl TemplateValue for Celsius {
+            fn to_value(&self) -> Value<'_> {
+                Value::from(format!("{}°C", Number::new(self.0).precision(1)))
+            }
+        }
+        assert_eq!(do_replace!("{{t}}", t = Celsius(21.456)), "21.5°C");
+    }
+
+    #[test]
+    fn test_lazy_values() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let expensive = Lazy::new(|| {
+            calls.set(calls.get() + 1);
+            vec![1, 2]
+        });
+
+        // Not referenced, or only in a section that is not rendered: never evaluated.
+        let template = String::from("{{#if show}}{{items}}{{/if}}");
+        assert_eq!(do_replace!(template, show = false, items = &expensive), "");
+        assert_eq!(do_replace!(String::from("plain"), items = &expensive), "plain");
+        assert!(!expensive.is_evaluated() && calls.get() == 0);
+
+        // Referenced several times, in a loop and across renders: evaluated once.
+        let template = String::from("{{items}}|{{#each items as i}}{{i}}{{items.0}};{{/each}}");
+        assert_eq!(do_replace!(template.clone(), items = &expensive), "1, 2|11;21;");
+        assert_eq!(do_replace!(template, items = &expensive), "1, 2|11;21;");
+        assert_eq!(calls.get(), 1);
+        assert_eq!(format!("{expensive:?}"), "Lazy([1, 2])");
+
+        // Works from any context, such as a registry rendering partials.
+        let mut registry = TemplateRegistry::new();
+        registry.register("page", "{{> footer}}{{> footer}}").register("footer", "[{{year}}]");
+        let year = Lazy::new(|| {
+            calls.set(calls.get() + 1);
+            2025
+        });
+        assert_eq!(registry.render("page", &[("year", &year)]).unwrap(), "[2025][2025]");
+        assert_eq!(calls.get(), 2);
+
+        // The closure runs once, so it can move a captured value into the result.
+        let rows = vec![String::from("a"), String::from("b")];
+        let calls = &calls;
+        let moved = Lazy::new(move || {
+            calls.set(calls.get() + 1);
+            rows
+        });
+        assert_eq!(do_replace!(String::from("{{rows}} {{rows.1}}"), rows = &moved), "a, b b");
+        assert_eq!(moved.get(), &["a", "b"]);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_multi_stage_rendering() {
+        use std::collections::HashMap;
+
+        // Rendering in two stages gives the same text as rendering once with every value.
+        fn check(template: Template, first: &[(&str, Value<'_>)], second: &[(&str, Value<'_>)]) -> String {
+            let staged = template.resolve(first);
+            let all: Vec<(&str, Value<'_>)> = first.iter().chain(second).cloned().collect();
+            assert_eq!(staged.render(second), template.render(&all), "staged source: {}", staged.source());
+            staged.source().to_string()
+        }
+
+        let text = |text: &'static str| Value::from(text);
+        let list = |items: &[&'static str]| Value::List(items.iter().map(|item| Value::from(*item)).collect());
+
+        // Values that look like tags or literal tags stay literal.
+        let source = check(
+            Template::new("{{a}}{{b}}|{{a}}"),
+            &[("a", text("{{b}} {{\"{{\"}} {")), ("x", text(""))],
+            &[("b", text("B"))],
+        );
+        assert_eq!(source, r#"{{"{{"}}b}} {{"{{"}}"{{"{{"}}"}} {{{b}}|{{"{{"}}b}} {{"{{"}}"{{"{{"}}"}} {"#);
+        check(Template::new("{{a}}{{b}}"), &[("a", text("\\"))], &[("b", text("B"))]);
+        check(Template::new("{{a}}{{c}}\"}}{{b}}"), &[("a", text("{")), ("c", text("{\""))], &[("b", text("B"))]);
+        check(Template::new("{{a}}{{{b}}}"), &[("a", text("{"))], &[("b", text("B"))]);
+
+        // Sections and loops are evaluated when their value is supplied, and kept otherwise.
+        let template = Template::new(
+            "{{#if show}}[{{x}}]{{else}}none{{/if}}{{#unless hide}}({{y}}){{/unless}}\
+             {{#each rows as row}}{{@index}}:{{row}}{{x}};{{else}}empty{{/each}}",
+        );
+        for (first, second) in [
+            (vec![("show", Value::Bool(true)), ("rows", list(&["a", "b"]))], vec![("x", text("X")), ("hide", Value::Bool(false)), ("y", text("Y"))]),
+            (vec![("x", text("X")), ("hide", Value::Bool(true)), ("row", text("outer"))], vec![("show", Value::Bool(false)), ("rows", list(&["c"]))]),
+            (vec![("rows", list(&[]))], vec![("show", Value::Bool(true)), ("x", text("{{y}}"))]),
+        ] {
+            check(template.clone(), &first, &second);
+        }
+        let source = check(template.clone(), &[("x", text("X"))], &[]);
+        assert_eq!(source, "{{#if show}}[X]{{else}}none{{/if}}{{#unless hide}}({{y}}){{/unless}}{{#each rows as row}}{{@index}}:{{row}}X;{{else}}empty{{/each}}");
+
+        // Trim markers are applied in the first stage, comments dropped, and escaping done once.
+        let template = Template::new("<p>\n  {{- name -}}\n</p>{{! note }} {{-  greeting }} {{#if ok -}}\n  {{city|upper}}\n{{- /if}}").with_escape(Escape::Html);
+        let source = check(template.clone(), &[("name", text("<b>"))], &[("greeting", text("hi")), ("ok", Value::Bool(true)), ("city", text("rome"))]);
+        assert_eq!(source, "<p>&lt;b&gt;</p>{{greeting}} {{#if ok}}{{city|upper}}{{/if}}");
+        check(template, &[("ok", Value::Bool(true))], &[("name", text("n")), ("greeting", text("g")), ("city", text("c"))]);
+
+        // Fallbacks, required markers, filters and missing paths behave as in a single pass.
+        let template = Template::new("{{a ? \"none\"}} {{b!}} {{c|round:1}} {{d.e}} {{f|nope}}");
+        check(template.clone(), &[("a", Value::Null), ("c", Value::Float(1.25))], &[("b", text("B")), ("d", text("D"))]);
+        let staged = template.resolve(&[("d", text("D")), ("f", text("F"))]);
+        assert_eq!(staged.source(), "{{a ? \"none\"}} {{b!}} {{c|round:1}} {{d.e}} {{f|nope}}");
+        let error = staged.render_strict(&[("a", text("A")), ("b", text("B")), ("c", Value::Int(2))]).unwrap_err();
+        assert_eq!(error.issues().len(), 2, "{error}");
+
+        // Partials and layouts are kept for the registry of the last stage.
+        let mut registry = TemplateRegistry::new();
+        registry.register("base", "<h1>{{#block title}}Site{{/block}}</h1>{{> footer year=year}}").register("footer", "<small>{{brand}} {{year}}</small>");
+        let page = Template::new("{{extends \"base\"}}{{#block title}}{{super()}}: {{page}}{{/block}}");
+        let staged = page.resolve(&[("page", "Blog")]);
+        assert_eq!(staged.source(), "{{extends \"base\"}}{{#block title}}{{super()}}: Blog{{/block}}");
+        registry.register_template("page", staged);
+        let mut values = HashMap::new();
+        values.insert("brand", "CANS");
+        values.insert("year", "2025");
+        assert_eq!(registry.render("page", &values).unwrap(), "<h1>Site: Blog</h1><small>CANS 2025</small>");
+
+        // Custom delimiters are escaped and written back the same way.
+        let template = Template::new("[[a]] {{b}} [[c]]").with_delimiters(Delimiters::new("[[", "]]"));
+        let staged = template.resolve(&[("a", "[[c]]")]);
+        assert_eq!((staged.source(), staged.render(&[("c", "C")]).as_str()), (r#"[["[["]]c]] {{b}} [[c]]"#, "[[c]] {{b}} C"));
+    }
+
+    #[test]
+    fn test_derive_context_with_a_crate_path() {
+        // Crates that reach cans through a re-export or under another name point the derive at it.
+        mod facade {
+            pub use cans as engine;
+        }
+        extern crate cans as my_cans;
+
+        #[derive(facade::engine::Context)]
+        #[cans(crate = "facade::engine")]
+        struct Card<T> {
+            title: &'static str,
+            #[cans(rename = "count")]
+            tags: Vec<T>,
+        }
+
+        #[derive(my_cans::Context)]
+        #[cans(crate = "my_cans")]
+        struct Page<T> {
+            #[cans(flatten)]
+            card: Card<T>,
+            author: &'static str,
+        }
+
+        let page = Page { card: Card { title: "Home", tags: vec![1, 2] }, author: "Sara" };
+        assert_eq!(Template::new("{{title}} {{count|join:\"+\"}} {{author}}").render(&page), "Home 1+2 Sara");
+        assert_eq!(do_html!("<h1>{{title}}</h1>", ..page), "<h1>Home</h1>");
+    }
 }